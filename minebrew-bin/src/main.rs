@@ -1,7 +1,20 @@
-use std::{io::Write, fs::OpenOptions};
+mod progress;
 
-use minebrew_lib::modrinth::{ Minebrew, Search, SearchResult };
-use minebrew_cfg::{ Options, Subcommands };
+use std::io::Write;
+
+use minebrew_lib::modrinth::{ Minebrew, MinebrewBuilder, Search, Pick, Candidate, ProjectType, Side, Version, VersionType, parse_query, looks_like_slug };
+use minebrew_lib::state::{Pin, State};
+use minebrew_lib::cache::{Cache, EntryKind};
+use minebrew_lib::audit::audit;
+use minebrew_lib::license::{self, Concern, LicensePolicy};
+use minebrew_cfg::{ Options, Globals, OutputFormat, Subcommands, InstallOpts, PinOpts, SearchOpts, InfoOpts, ChangelogOpts, AuditOpts, LicensesOpts, ProfileOpts, CacheOpts, ConfigOpts, ConfigFile, Settings, VersionRange, exit_with_msg };
+use minebrew_cfg::instances;
+
+use progress::Bars;
+
+use futures::stream::{self, StreamExt};
+
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() {
@@ -9,78 +22,965 @@ async fn main() {
     let opts = Options::parse();
 
     match opts.command {
-        Subcommands::Install(_) => install(opts),
+        Subcommands::Install(_) => install(opts).await,
+        Subcommands::Update(_) => update(opts).await,
+        Subcommands::Search(s) => search(s, &opts.globals).await,
+        Subcommands::Info(i) => info(i, &opts.globals).await,
+        Subcommands::Changelog(c) => changelog(c, &opts.globals).await,
+        Subcommands::Audit(a) => audit_installed(a, &opts.globals).await,
+        Subcommands::Licenses(l) => licenses(l, &opts.globals).await,
+        Subcommands::Pin(p) => pin(p, &opts.globals).await,
+        Subcommands::Unpin(p) => unpin(p, &opts.globals),
+        Subcommands::Profile(p) => profile(p, &opts.globals),
+        Subcommands::Instances => list_instances(&opts.globals),
+        Subcommands::Cache(c) => cache(c, &opts.globals),
+        Subcommands::Config(c) => config(c, opts.settings, &opts.globals),
         _ => todo!(),
-    }.await
+    }
+}
+
+/// Sizes in bytes the way people read them, ex: 12.3 MiB
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Print a list of results as pretty json
+fn print_json(value: serde_json::Value) {
+    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+}
+
+/// Quote a value for a csv file if it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A Minebrew set up the way the global options ask
+fn client(globals: &Globals) -> MinebrewBuilder {
+    Minebrew::builder()
+        .api_url(&globals.api_url)
+        .token(globals.token.clone())
+        .proxy(globals.proxy.clone())
+        .no_proxy(globals.no_proxy.clone())
+        .root_certificates(globals.ca_certs.clone())
+        // the setting has already been checked to be native or rustls
+        .tls(globals.tls.parse().unwrap())
+        .offline(globals.offline)
+        .refresh(globals.refresh)
+        .ttl(globals.cache_ttl)
+        .concurrency(globals.concurrency)
+        .timeout(globals.timeout)
+}
+
+/// Expand the target, which may be a range like "1.19.x", into every 
+/// minecraft version modrinth knows about that it covers
+async fn target_versions(mbrew: &Minebrew, target: &str) -> Vec<String> {
+    // clap has already made sure the target is valid
+    let range: VersionRange = target.parse().unwrap();
+    if let Some(version) = range.exact() {
+        return vec![version.to_string()];
+    }
+
+    let known = mbrew.game_versions().await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let versions = range.expand(known.iter().map(|v| v.version.as_str()));
+
+    if versions.is_empty() {
+        exit_with_msg(format!("error: no minecraft versions match {target}"));
+    }
+    versions
+}
+
+/// The version of `slug` that's installed, taken from `versions` or 
+/// fetched if it isn't one of them. `None` if it isn't installed
+async fn installed_version(mbrew: &Minebrew, state: &State, slug: &str, versions: &[Version]) -> Option<Version> {
+    let installed = state.installed.get(slug)?;
+    if let Some(v) = versions.iter().find(|v| v.id == installed.version_id) {
+        return Some(v.clone());
+    }
+
+    mbrew.version(&installed.version_id).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")))
+}
+
+/// Print the changelog of every version in `changes`
+fn print_changelogs(changes: &[Version]) {
+    for v in changes {
+        let date = v.date_published.get(..10).unwrap_or(&v.date_published);
+        println!("\n{} ({}, {date})", v.version_number, v.version_type);
+        match (&v.changelog, &v.changelog_url) {
+            (Some(log), _) if !log.trim().is_empty() => println!("{}", log.trim()),
+            (_, Some(url)) => println!("See {url}"),
+            _ => println!("No changelog"),
+        }
+    }
+}
+
+/// What an install or update is resolved against
+struct Setup {
+    project_type: ProjectType,
+    loaders: Vec<String>,
+    side: Option<Side>,
+    channel: VersionType,
+    // where the files will end up, relative to the minecraft directory
+    folder: PathBuf,
+    targets: Vec<String>,
+}
+
+/// Work out what to install for from the install or update options
+async fn setup(mbrew: &Minebrew, i_opts: &InstallOpts) -> Setup {
+    // clap has already made sure the type is valid
+    let project_type: ProjectType = i_opts.project_type.parse().unwrap();
+
+    // the profile's side and channel were validated when the config was loaded
+    let side: Option<Side> = i_opts.side.as_deref().map(|s| s.parse().unwrap());
+    let channel: VersionType = i_opts.channel.as_deref()
+        .map_or(VersionType::Release, |c| c.parse().unwrap());
+
+    let folder = project_type.install_dir(&i_opts.mc_dir, i_opts.world.as_deref())
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    Setup {
+        project_type,
+        loaders: project_type.loaders_for(i_opts.loader.as_deref()),
+        side,
+        channel,
+        folder,
+        targets: target_versions(mbrew, &i_opts.target).await,
+    }
+}
+
+/// Let the user know which mods are being held back by a pin
+fn report_held_back(candidates: &[Candidate]) {
+    candidates.iter()
+        .filter_map(|c| c.held_back.as_ref().map(|latest| (c, latest)))
+        .for_each(|(c, latest)| println!(
+            "{} is pinned to {}, not upgrading to {}",
+            c.slug, c.version.version_number, latest
+        ));
 }
 
 async fn install(mut opts: Options) {
-    let mbrew = Minebrew::default();
+    let globals = &opts.globals;
+    let mut mbrew = client(globals);
+    if !globals.quiet() {
+        mbrew = mbrew.progress(Bars::new(globals.verbose()));
+    }
+    let mbrew = mbrew.build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
     // unwraping is okay here because we should never not 
     // pass any other Subcommand variant other than Install
     let i_opts = opts.command.install_opts().unwrap();
 
+    let mut state = State::load(&i_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    let setup = setup(&mbrew, &i_opts).await;
+    let Setup { project_type, loaders, side, channel, targets, .. } = &setup;
+    let (project_type, side, channel) = (*project_type, *side, *channel);
+
+    if globals.verbose() {
+        println!("Installing into {} for minecraft {} ({} loader)",
+            i_opts.mc_dir.display(), targets.join(", "), i_opts.loader.as_deref().unwrap_or("any"));
+    }
+
+    // queries of the form slug@version skip searching entirely
+    let (exact, fuzzy): (Vec<_>, Vec<_>) = i_opts.queries.iter()
+        .map(|q| parse_query(q))
+        .partition(|(_, version)| version.is_some());
+
+    // exact slugs, project ids and pasted urls are looked up 
+    // directly, anything modrinth doesn't know about gets searched
+    let mut slugs = Vec::with_capacity(fuzzy.len());
+    let mut to_search = Vec::new();
+    let mut choices_changed = false;
+    for (query, _) in fuzzy {
+        // --forget throws away the result picked the last time so it's asked again
+        if i_opts.forget && state.forget_choice(project_type, query).is_some() {
+            choices_changed = true;
+        }
+
+        // a result picked the last time this was searched for is reused
+        let chosen = state.choice(project_type, query).map(str::to_string);
+        let project = match chosen.as_deref() {
+            Some(slug) => mbrew.project(slug).await
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}"))),
+            None if looks_like_slug(query) => mbrew.project(query).await
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}"))),
+            None => None,
+        };
+
+        // a project of the wrong type or for the wrong 
+        // side is probably not what they meant
+        match project {
+            Some(p) if p.project_type == project_type
+                && side.is_none_or(|s| s.supported(p.client_side, p.server_side)) => slugs.push(p.slug),
+            _ => {
+                // a remembered pick that doesn't fit anymore is asked again
+                if chosen.is_some() {
+                    state.forget_choice(project_type, query);
+                    choices_changed = true;
+                }
+                to_search.push(query.to_string());
+            },
+        }
+    }
+
     // Loop through every query made 
     // Turns quries into ModFile structs which have a download link
-    let searches = Search::new(&i_opts.queries, &i_opts.target);
+    let mut searches = Search::new(&to_search, targets, project_type, loaders.clone());
+    searches.side = side;
 
-    println!("Searching modrinth for {} mods", &i_opts.target);
+    if !to_search.is_empty() && !globals.quiet() {
+        println!("Searching modrinth for {} {}s", &i_opts.target, project_type);
+    }
 
-    // Make requests and serialize them
-    let resps = mbrew.search(&searches).await;
+    // Make requests and serialize them, every query that 
+    // couldn't be searched is reported before giving up
+    let mut resps = Vec::with_capacity(to_search.len());
+    let mut failed = false;
+    for resp in mbrew.search(&searches).await {
+        match resp {
+            Ok(sr) => resps.push(sr),
+            Err(e) => {
+                eprintln!("error: {e:#}");
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
 
     // filter out and choose search results
-    let results: Vec<SearchResult> = resps.into_iter()
-        .map(|mut sr| { 
-            // filter out search results from each response
-            sr.filter(2);
-            sr.pick_result()
-        }).collect();
+    for mut sr in resps {
+        // filter out and sort search results from each response
+        sr.rank();
+
+        // with --yes the best match is taken without asking
+        if globals.yes {
+            let query = sr.query();
+            match sr.hits.into_iter().next() {
+                Some(res) => slugs.push(res.slug),
+                None => exit_with_msg(format!("error: {query} not found")),
+            }
+            continue;
+        }
 
-    let files = mbrew.files_from_results(&results, &i_opts.target).await;
+        loop {
+            let asked = sr.hits.len() > 1;
+            match sr.pick_result() {
+                Pick::Chosen(res) => {
+                    if asked {
+                        state.remember_choice(project_type, sr.query(), &res.slug);
+                        choices_changed = true;
+                    }
+                    slugs.push(res.slug);
+                    break;
+                },
+                Pick::Skip => break,
+                Pick::NotFound => exit_with_msg(format!("error: {} not found", sr.query())),
+                Pick::More => {
+                    let next = mbrew.search_page(&searches, sr.query(), sr.offset + sr.limit).await
+                        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+                    sr.extend(next);
+                    sr.rank();
+                },
+            }
+        }
+    }
+
+    // save choices now so they're kept even if installation is cancelled
+    if choices_changed {
+        state.save().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    }
+
+    let mut candidates = mbrew.files_from_slugs(&slugs, targets, loaders, channel, &state).await
+        .unwrap_or_else(|e| {
+            e.to_string().lines().for_each(|line| eprintln!("error: {line}"));
+            std::process::exit(1);
+        });
+
+    for (slug, version) in exact {
+        // partitioned above so the version is always there
+        let version = version.unwrap();
+        match mbrew.exact_version(slug, version, targets, loaders, &state).await {
+            Ok(candidate) => candidates.push(candidate),
+            Err(e) => {
+                eprintln!("error: {e:#}");
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+
+    report_held_back(&candidates);
+    finish_install(&mbrew, globals, &i_opts, &setup, &mut state, candidates).await;
+}
+
+async fn update(mut opts: Options) {
+    let globals = &opts.globals;
+    let mut mbrew = client(globals);
+    if !globals.quiet() {
+        mbrew = mbrew.progress(Bars::new(globals.verbose()));
+    }
+    let mbrew = mbrew.build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    // only ever called for Subcommands::Update
+    let i_opts = opts.command.install_opts().unwrap();
+
+    let mut state = State::load(&i_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let setup = setup(&mbrew, &i_opts).await;
+
+    // only what's installed in the folder projects of this type go in
+    let installed: Vec<&str> = state.installed.iter()
+        .filter(|(_, m)| m.folder == setup.folder)
+        .map(|(slug, _)| slug.as_str())
+        .collect();
+    let slugs: Vec<String> = match i_opts.queries.is_empty() {
+        true => installed.iter().map(|s| s.to_string()).collect(),
+        false => i_opts.queries.iter().map(|q| match state.find_slug(q) {
+            Some(slug) if installed.contains(&slug) => slug.to_string(),
+            _ => exit_with_msg(format!("error: {q} isn't an installed {}", setup.project_type)),
+        }).collect(),
+    };
+
+    if slugs.is_empty() {
+        return println!("No {}s are installed", setup.project_type);
+    }
+
+    // pinned mods resolve to their pin
+    let mut candidates = mbrew.files_from_slugs(&slugs, &setup.targets, &setup.loaders, setup.channel, &state).await
+        .unwrap_or_else(|e| {
+            e.to_string().lines().for_each(|line| eprintln!("error: {line}"));
+            std::process::exit(1);
+        });
+
+    report_held_back(&candidates);
+    candidates.retain(|c| state.installed.get(&c.slug).is_none_or(|m| m.version_id != c.version.id));
+    if candidates.is_empty() {
+        if !globals.quiet() {
+            println!("Everything is up to date");
+        }
+        return;
+    }
+
+    finish_install(&mbrew, globals, &i_opts, &setup, &mut state, candidates).await;
+}
+
+/// Check the mods about to be installed against pins and the license 
+/// policy, ask to go ahead, then download them and record them in `state`
+async fn finish_install(mbrew: &Minebrew, globals: &Globals, i_opts: &InstallOpts, setup: &Setup, state: &mut State, candidates: Vec<Candidate>) {
+    let Setup { loaders, targets, folder, .. } = setup;
+
+    // a mod that needs a different version of a pinned mod can't be installed
+    if let Err(e) = mbrew.check_dependency_pins(&candidates, state).await {
+        e.to_string().lines().for_each(|line| eprintln!("error: {line}"));
+        std::process::exit(1);
+    }
+
+    // refuse every mod the license policy doesn't allow before anything is downloaded
+    let policy = LicensePolicy { allow: i_opts.allow_licenses.clone(), deny: i_opts.deny_licenses.clone() };
+    if !policy.is_empty() {
+        let ids: Vec<String> = candidates.iter().map(|c| c.version.project_id.clone()).collect();
+        let projects = mbrew.projects(&ids).await
+            .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+        let mut failed = false;
+        for c in &candidates {
+            let license = projects.iter()
+                .find(|p| p.id == c.version.project_id)
+                .and_then(|p| p.license.as_ref());
+            let id = license::spdx_id(license);
+            if !policy.allows(id) {
+                eprintln!("error: {} is licensed {id}, which the license policy doesn't allow", c.slug);
+                failed = true;
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
+    }
+
+    // show what every upgrade brings so it can be reviewed before saying yes
+    if i_opts.changelog {
+        for c in &candidates {
+            if state.installed.get(&c.slug).is_none_or(|m| m.version_id == c.version.id) {
+                continue;
+            }
+
+            let versions = mbrew.project_versions(&c.slug, loaders, targets).await
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")))
+                .unwrap_or_default();
+            if let Some(installed) = installed_version(mbrew, state, &c.slug, &versions).await {
+                println!("\n{} {} -> {}", c.slug, installed.version_number, c.version.version_number);
+                print_changelogs(Version::changes_between(&versions, &installed, &c.version.id));
+            }
+        }
+    }
 
     // List all the mods ready to be downloaded and ask
     // the user to confirm
     let mut chars_left: usize = 0;
-    println!("\nMods ({})", files.len());
-    files.iter().for_each(|f| {
-        match chars_left.checked_sub(&f.filename.len() + 2) {
+    println!("\nMods ({})", candidates.len());
+    candidates.iter().map(|c| &c.file).for_each(|f| {
+        match chars_left.checked_sub(f.filename.len() + 2) {
             Some(left) => {
                 print!("{}  ", f);
                 chars_left = left;
             },
             None => {
                 print!("\n\t{}  ", f);
-                chars_left = 80 - &f.filename.len();
+                chars_left = 80 - f.filename.len();
             },
         }
     });
 
-    print!("\n\nBegin Installation? [y/n]");
-    std::io::stdout().flush().unwrap(); // flush buffer to print everything
+    if globals.yes {
+        println!();
+    } else {
+        print!("\n\nBegin Installation? [y/n]");
+        std::io::stdout().flush().unwrap(); // flush buffer to print everything
 
-    // get user input
-    let mut input = String::with_capacity(1);
-    std::io::stdin().read_line(&mut input).unwrap();
-    match input.trim().chars().nth(0) {
-        Some('y') | Some('Y') | None => {},
-        _ => std::process::exit(1),
+        // get user input
+        let mut input = String::with_capacity(1);
+        std::io::stdin().read_line(&mut input).unwrap();
+        match input.trim().chars().next() {
+            Some('y') | Some('Y') | None => {},
+            _ => std::process::exit(1),
+        };
+    }
+
+    // path to the mods, resourcepacks, shaderpacks or datapacks folder
+    let install_dir = i_opts.mc_dir.join(folder);
+
+    if !globals.quiet() {
+        println!("\nSearching for {} folder...", folder.display());
+    }
+    // if the folder doesn't exist then make one
+    if !install_dir.exists() {
+        if !globals.quiet() {
+            println!("Not found, creating {} folder...", folder.display());
+        }
+        std::fs::create_dir_all(&install_dir).unwrap();
+    } else if !globals.quiet() {
+        println!("Folder found...")
+    }
+
+    // download all the files we've gathered
+    let files: Vec<_> = candidates.iter().map(|c| &c.file).collect();
+    if let Err(e) = mbrew.download_files(&files, &install_dir).await {
+        exit_with_msg(format!("error: {e:#}"));
+    }
+
+    // remember what we installed, removing files from
+    // versions that have just been replaced
+    for c in &candidates {
+        if let Some(old) = state.installed.get(&c.slug) {
+            if old.filename != c.file.filename || old.folder != *folder {
+                std::fs::remove_file(i_opts.mc_dir.join(&old.folder).join(&old.filename)).ok();
+            }
+        }
+        state.record(&c.slug, &c.version, &c.file, folder);
+    }
+    state.save().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    println!("Success!");
+}
+
+async fn search(s_opts: SearchOpts, globals: &Globals) {
+    let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    // clap has already made sure the type is valid
+    let project_type: ProjectType = s_opts.project_type.parse().unwrap();
+    let loaders = project_type.loaders_for(s_opts.loader.as_deref());
+
+    let targets = target_versions(&mbrew, &s_opts.target).await;
+    let mut search = Search::new(&[], &targets, project_type, loaders);
+    search.limit = s_opts.limit;
+
+    let resp = mbrew.search_page(&search, &s_opts.query, 0).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    if globals.format == OutputFormat::Json {
+        return print_json(serde_json::json!(resp.hits));
+    }
+
+    if resp.hits.is_empty() {
+        exit_with_msg(format!("No {}s found for \"{}\"", project_type, s_opts.query));
+    }
+
+    resp.hits.iter().enumerate()
+        .for_each(|(i, r)| println!("{}", r.summary(i + 1)));
+}
+
+async fn info(i_opts: InfoOpts, globals: &Globals) {
+    let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let state = State::load(&i_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    let name = state.find_slug(&i_opts.name).unwrap_or(&i_opts.name);
+    let project = match mbrew.project(name).await {
+        Ok(Some(project)) => project,
+        Ok(None) => exit_with_msg(format!("error: modrinth has no project {name}")),
+        Err(e) => exit_with_msg(format!("error: {e:#}")),
     };
+    let versions = mbrew.project_versions(&project.id, &[], &[]).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")))
+        .unwrap_or_default();
+    let latest = Version::latest_per_target(&versions);
+
+    let installed = state.installed.iter()
+        .find(|(slug, m)| **slug == project.slug || m.project_id == project.id)
+        .map(|(_, m)| m);
+
+    if globals.format == OutputFormat::Json {
+        return print_json(serde_json::json!({
+            "project": project,
+            "latest": latest.iter()
+                .flat_map(|(loader, targets)| targets.iter().map(move |(game_version, v)| serde_json::json!({
+                    "loader": loader,
+                    "game_version": game_version,
+                    "version_id": v.id,
+                    "version_number": v.version_number,
+                })))
+                .collect::<Vec<_>>(),
+            "installed": installed,
+        }));
+    }
+
+    println!("{} ({})", project.title, project.slug);
+    println!("{}\n", project.description);
+
+    let row = |field: &str, value: &str| println!("{field:<13}{value}");
+    let mut categories = project.categories.clone();
+    categories.extend(project.additional_categories.iter().cloned());
+    row("Type", project.project_type.as_str());
+    row("Categories", &categories.join(", "));
+    row("License", &match &project.license {
+        Some(l) => l.url.as_ref().map_or(l.id.clone(), |url| format!("{} ({url})", l.id)),
+        None => "unknown".to_string(),
+    });
+    row("Client", &project.client_side.to_string());
+    row("Server", &project.server_side.to_string());
+    row("Status", &project.status.to_string());
+    row("Downloads", &project.downloads.to_string());
+
+    let links = [
+        ("Source", &project.source_url),
+        ("Issues", &project.issues_url),
+        ("Wiki", &project.wiki_url),
+        ("Discord", &project.discord_url),
+    ];
+    for (field, url) in links {
+        if let Some(url) = url {
+            row(field, url);
+        }
+    }
+    for donation in &project.donation_urls {
+        row("Donate", &format!("{} ({})", donation.url, donation.platform));
+    }
+
+    row("Installed", &match installed {
+        Some(m) => match state.pin(&project.slug) {
+            Some(pin) => format!("{} ({}, pinned to {})", m.version_number, m.filename, pin.version_number),
+            None => format!("{} ({})", m.version_number, m.filename),
+        },
+        None => "no".to_string(),
+    });
+
+    if latest.is_empty() {
+        return;
+    }
+
+    // only the newest few minecraft versions unless -v is passed
+    const SHOWN: usize = 5;
+    println!("\nLatest versions");
+    for (loader, targets) in latest {
+        println!("  {loader}");
+        let shown = if globals.verbose() { targets.len() } else { SHOWN };
+        for (game_version, v) in targets.iter().take(shown) {
+            println!("    {game_version:<11}{} ({})", v.version_number, v.version_type);
+        }
+        if targets.len() > shown {
+            println!("    ... and {} more, use -v to see them all", targets.len() - shown);
+        }
+    }
+}
+
+async fn changelog(c_opts: ChangelogOpts, globals: &Globals) {
+    let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let state = State::load(&c_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    let slug = state.find_slug(&c_opts.name).unwrap_or(&c_opts.name);
+
+    // clap has already made sure the type is valid
+    let project_type: ProjectType = c_opts.project_type.parse().unwrap();
+    let loaders = project_type.loaders_for(c_opts.loader.as_deref());
+    // the profile's channel was validated when the config was loaded
+    let channel: VersionType = c_opts.channel.as_deref()
+        .map_or(VersionType::Release, |c| c.parse().unwrap());
+
+    let targets = target_versions(&mbrew, &c_opts.target).await;
+    let versions = match mbrew.project_versions(slug, &loaders, &targets).await {
+        Ok(Some(versions)) => versions,
+        Ok(None) => exit_with_msg(format!("error: modrinth has no project {slug}")),
+        Err(e) => exit_with_msg(format!("error: {e:#}")),
+    };
+    let candidate = Version::select(slug, versions.clone(), state.pin(slug), channel)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    // a mod that isn't installed only has the newest version's changes to show
+    let installed = installed_version(&mbrew, &state, slug, &versions).await;
+    let changes = match &installed {
+        Some(installed) => Version::changes_between(&versions, installed, &candidate.version.id),
+        None => std::slice::from_ref(&candidate.version),
+    };
+
+    if globals.format == OutputFormat::Json {
+        return print_json(changes.iter().map(|v| serde_json::json!({
+            "id": v.id,
+            "version_number": v.version_number,
+            "version_type": v.version_type,
+            "date_published": v.date_published,
+            "changelog": v.changelog,
+            "changelog_url": v.changelog_url,
+        })).collect());
+    }
+
+    match &installed {
+        Some(installed) if changes.is_empty() => 
+            return println!("{slug} is up to date ({})", installed.version_number),
+        Some(installed) => println!("{slug} {} -> {}", installed.version_number, candidate.version.version_number),
+        None => println!("{slug} isn't installed, showing the changes in {}", candidate.version.version_number),
+    }
+    print_changelogs(changes);
+}
+
+async fn audit_installed(a_opts: AuditOpts, globals: &Globals) {
+    let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let state = State::load(&a_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let targets = target_versions(&mbrew, &a_opts.target).await;
+
+    // every project and every installed version is fetched in one request
+    let project_ids: Vec<String> = state.installed.values().map(|m| m.project_id.clone()).collect();
+    let version_ids: Vec<String> = state.installed.values().map(|m| m.version_id.clone()).collect();
+    let projects = mbrew.projects(&project_ids).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let versions = mbrew.versions(&version_ids).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
 
-    // path to mods folder
-    let mods_folder = i_opts.mc_dir.join("mods");
+    // the newest version takes a request per mod, a few of them at a time
+    let (mbrew, projects, versions, targets) = (&mbrew, &projects, &versions, &targets);
+    let loader = a_opts.loader.as_deref();
+    let mut report: Vec<_> = stream::iter(&state.installed).map(|(slug, installed)| async move {
+        let project = projects.iter().find(|p| p.id == installed.project_id);
+        let version = versions.iter().find(|v| v.id == installed.version_id);
 
-    println!("\nSearching for mods folder...");
-    // if mods folder doesn't exist then make one
-    if !mods_folder.exists() {
-    println!("Not found, creating mods folder...");
-        std::fs::create_dir_all(&mods_folder).unwrap();
+        // the newest version for the loader, whichever minecraft version it's for
+        let latest = match project {
+            Some(p) => mbrew.project_versions(&p.id, &p.project_type.loaders_for(loader), &[]).await
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")))
+                .and_then(|vers| vers.into_iter().next()),
+            None => None,
+        };
+
+        (slug, installed, audit(project, version, latest.as_ref(), targets))
+    }).buffer_unordered(globals.concurrency).collect().await;
+    report.sort_by(|a, b| a.0.cmp(b.0));
+
+    if globals.format == OutputFormat::Json {
+        print_json(report.iter().map(|(slug, installed, issues)| serde_json::json!({
+            "slug": slug,
+            "version_number": installed.version_number,
+            "issues": issues.iter().map(|i| serde_json::json!({
+                "kind": i.kind(),
+                "message": i.to_string(),
+            })).collect::<Vec<_>>(),
+        })).collect());
     } else {
-        println!("Mods folder found...")
+        for (slug, installed, issues) in report.iter().filter(|(_, _, issues)| !issues.is_empty()) {
+            println!("{slug} {}", installed.version_number);
+            issues.iter().for_each(|i| println!("  - {i}"));
+        }
     }
 
-    // download all the files we've gathered
-    mbrew.download_files(&files, &mods_folder).await;
+    // failing lets scripts notice there's something to look at
+    let flagged = report.iter().filter(|(_, _, issues)| !issues.is_empty()).count();
+    if flagged > 0 {
+        if globals.format == OutputFormat::Text {
+            println!("\n{flagged} of {} installed mods need attention", report.len());
+        }
+        std::process::exit(1);
+    }
+
+    if globals.format == OutputFormat::Text && !globals.quiet() {
+        println!("All {} installed mods look fine", report.len());
+    }
+}
+
+async fn licenses(l_opts: LicensesOpts, globals: &Globals) {
+    let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let state = State::load(&l_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+    let policy = LicensePolicy { allow: l_opts.allow_licenses, deny: l_opts.deny_licenses };
+
+    let ids: Vec<String> = state.installed.values().map(|m| m.project_id.clone()).collect();
+    let projects = mbrew.projects(&ids).await
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    // one row per installed mod, grouped by license
+    let mut rows: Vec<_> = state.installed.iter().map(|(slug, m)| {
+        let license = projects.iter()
+            .find(|p| p.id == m.project_id)
+            .and_then(|p| p.license.as_ref());
+        (slug, m, license::spdx_id(license).to_string(), license::url(license))
+    }).collect();
+    rows.sort_by(|a, b| a.2.cmp(&b.2));
+
+    // what about a license needs a closer look, anything the policy refuses first
+    let concern = |id: &str| match policy.allows(id) {
+        true => license::concern(id),
+        false => Some(Concern::NotAllowed),
+    };
+
+    match globals.format {
+        // a table that's easy to hand over for review
+        _ if l_opts.csv => {
+            println!("slug,version,license,url,concern");
+            for (slug, m, id, url) in &rows {
+                let concern = concern(id).map(|c| c.to_string()).unwrap_or_default();
+                let fields = [slug.as_str(), &m.version_number, id, url.as_deref().unwrap_or(""), &concern];
+                println!("{}", fields.map(csv_field).join(","));
+            }
+        },
+        OutputFormat::Json => print_json(rows.iter().map(|(slug, m, id, url)| serde_json::json!({
+            "slug": slug,
+            "version_number": m.version_number,
+            "license": id,
+            "url": url,
+            "concern": concern(id).map(|c| c.to_string()),
+            "allowed": policy.allows(id),
+        })).collect()),
+        OutputFormat::Text => {
+            for group in rows.chunk_by(|a, b| a.2 == b.2) {
+                let (_, _, id, url) = &group[0];
+                let mut header = id.clone();
+                if let Some(url) = url {
+                    header.push_str(&format!(" {url}"));
+                }
+                if let Some(concern) = concern(id) {
+                    header.push_str(&format!("  [{concern}]"));
+                }
+
+                println!("{header}");
+                group.iter().for_each(|(slug, m, _, _)| println!("  {slug} {}", m.version_number));
+            }
+        },
+    }
+
+    // failing lets scripts notice something was installed before the policy refused it
+    if rows.iter().any(|(_, _, id, _)| !policy.allows(id)) {
+        std::process::exit(1);
+    }
+}
+
+async fn pin(p_opts: PinOpts, globals: &Globals) {
+    let mut state = State::load(&p_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+    let installed = state.find_slug(&p_opts.name).map(str::to_string);
+    let (slug, pin) = match (installed, p_opts.version) {
+        // without an explicit version pin whatever is installed right now
+        (Some(slug), None) => {
+            let m = &state.installed[&slug];
+            let pin = Pin { version_id: m.version_id.clone(), version_number: m.version_number.clone() };
+            (slug, pin)
+        },
+        (None, None) => exit_with_msg(format!(
+            "error: {} isn't installed, specify a version to pin it to", p_opts.name
+        )),
+        // make sure the project and version exist so a typo can't be pinned
+        (installed, Some(requested)) => {
+            let mbrew = client(globals).build().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+            let name = installed.as_deref().unwrap_or(&p_opts.name);
+            let project = match mbrew.project(name).await {
+                Ok(Some(project)) => project,
+                Ok(None) => exit_with_msg(format!("error: modrinth has no project {name}")),
+                Err(e) => exit_with_msg(format!("error: {e:#}")),
+            };
+            let versions = mbrew.project_versions(&project.id, &[], &[]).await
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")))
+                .unwrap_or_default();
+            match versions.iter().find(|v| v.matches(&requested)) {
+                Some(v) => (project.slug, Pin::new(v)),
+                None => exit_with_msg(format!("error: {} has no version {requested}", project.slug)),
+            }
+        },
+    };
+
+    if !globals.quiet() {
+        println!("Pinned {slug} to {}", pin.version_number);
+    }
+    state.pins.insert(slug, pin);
+    state.save().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+}
+
+fn unpin(p_opts: PinOpts, globals: &Globals) {
+    let mut state = State::load(&p_opts.mc_dir)
+        .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
 
-    println!("\nSuccess!");
+    let slug = state.find_slug(&p_opts.name)
+        .unwrap_or(&p_opts.name)
+        .to_string();
+
+    match state.pins.remove(&slug) {
+        Some(pin) if !globals.quiet() => println!("Unpinned {slug} from {}", pin.version_number),
+        Some(_) => {},
+        None => exit_with_msg(format!("error: {slug} isn't pinned")),
+    }
+    state.save().unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+}
+
+fn profile(p_opts: ProfileOpts, globals: &Globals) {
+    let done = |msg: String| if !globals.quiet() { println!("{msg}") };
+
+    let result = match p_opts {
+        ProfileOpts::Create { name, profile } => ConfigFile::create_profile(&name, &profile)
+            .map(|_| done(format!("Created profile {name}"))),
+        ProfileOpts::Switch(name) => ConfigFile::switch_profile(&name)
+            .map(|_| done(format!("Switched to profile {name}"))),
+        ProfileOpts::Remove(name) => ConfigFile::remove_profile(&name)
+            .map(|_| done(format!("Removed profile {name}"))),
+        ProfileOpts::List => {
+            let cfg_file = ConfigFile::load();
+            if globals.format == OutputFormat::Json {
+                return print_json(serde_json::json!({
+                    "active": cfg_file.profile,
+                    "profiles": cfg_file.profiles,
+                }));
+            }
+
+            if cfg_file.profiles.is_empty() {
+                println!("No profiles, create one with `mbrew profile create <name>`");
+            }
+
+            // the active profile is marked with a *
+            for (name, p) in &cfg_file.profiles {
+                let active = if cfg_file.profile.as_ref() == Some(name) { "*" } else { " " };
+                println!("{active} {name}\t{} {} {}\t{}",
+                    p.target,
+                    p.loader.as_deref().unwrap_or("any"),
+                    p.side.as_deref().unwrap_or("any side"),
+                    p.mc_dir.display());
+            }
+            Ok(())
+        },
+    };
+
+    result.unwrap_or_else(|e| exit_with_msg(format!("error: {e}")));
+}
+
+fn list_instances(globals: &Globals) {
+    let found = instances::discover();
+    if globals.format == OutputFormat::Json {
+        return print_json(serde_json::json!(found));
+    }
+
+    if found.is_empty() {
+        println!("No launcher instances found");
+    }
+
+    for i in found {
+        println!("{}\t{} {}\t({})\t{}",
+            i.name,
+            i.target.as_deref().unwrap_or("?"),
+            i.loader.as_deref().unwrap_or("vanilla"),
+            i.launcher,
+            i.mc_dir.display());
+    }
+}
+
+fn config(c_opts: ConfigOpts, settings: Settings, globals: &Globals) {
+    match c_opts {
+        ConfigOpts::Explain if globals.format == OutputFormat::Json => {
+            print_json(settings.explain().into_iter()
+                .map(|(field, value, source)| (field.to_string(), serde_json::json!({ "value": value, "source": source })))
+                .collect::<serde_json::Map<_, _>>().into());
+        },
+        ConfigOpts::Explain => {
+            for (field, value, source) in settings.explain() {
+                println!("{field:<16}{:<40}{source}", value.as_deref().unwrap_or("(not set)"));
+            }
+        },
+    }
+}
+
+fn cache(c_opts: CacheOpts, globals: &Globals) {
+    let cache = Cache::default();
+
+    match c_opts {
+        CacheOpts::List => {
+            let mut entries = cache.entries()
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+            entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+            if globals.format == OutputFormat::Json {
+                return print_json(entries.iter().map(|e| serde_json::json!({
+                    "kind": match e.kind {
+                        EntryKind::File => "file",
+                        EntryKind::Response => "response",
+                    },
+                    "filename": e.filename,
+                    "sha512": e.sha512,
+                    "size": e.size,
+                    "last_used": e.last_used.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs(),
+                })).collect());
+            }
+
+            // there's one response for every request ever made, so they're only counted
+            let (files, responses): (Vec<_>, Vec<_>) = entries.into_iter()
+                .partition(|e| e.kind == EntryKind::File);
+            for e in files {
+                println!("{}\t{}\t{}", &e.sha512[..e.sha512.len().min(12)], human_size(e.size), e.filename);
+            }
+            if !responses.is_empty() && !globals.quiet() {
+                println!("and {} cached responses ({})", responses.len(), human_size(responses.iter().map(|e| e.size).sum()));
+            }
+        },
+        CacheOpts::Size => {
+            let size = cache.size()
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+            println!("{} in {}", human_size(size), cache.root().display());
+        },
+        CacheOpts::Clean | CacheOpts::Prune { .. } => {
+            let removed = match c_opts {
+                CacheOpts::Prune { older_than } => cache.prune(older_than),
+                _ => cache.clean(),
+            };
+            let (files, bytes) = removed
+                .unwrap_or_else(|e| exit_with_msg(format!("error: {e:#}")));
+
+            if !globals.quiet() {
+                println!("Removed {files} files, freeing {}", human_size(bytes));
+            }
+        },
+    }
 }
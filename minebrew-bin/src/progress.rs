@@ -0,0 +1,77 @@
+use minebrew_lib::progress::Progress;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Draws a progress bar for every file being downloaded,
+/// one line each with its speed and how long it has left
+pub struct Bars {
+    multi: MultiProgress,
+
+    // keyed by filename
+    bars: Mutex<HashMap<String, ProgressBar>>,
+
+    // print each mod as it's resolved
+    verbose: bool,
+}
+
+impl Bars {
+    pub fn new(verbose: bool) -> Self {
+        Self { multi: MultiProgress::new(), bars: Mutex::new(HashMap::new()), verbose }
+    }
+
+    /// Run `f` on the bar for `filename` if it has one
+    fn with_bar(&self, filename: &str, f: impl FnOnce(&ProgressBar)) {
+        if let Some(bar) = self.bars.lock().unwrap().get(filename) {
+            f(bar);
+        }
+    }
+}
+
+fn style() -> ProgressStyle {
+    ProgressStyle::with_template("{msg:32!} [{bar:30}] {bytes:>10}/{total_bytes:<10} {bytes_per_sec:>12} eta {eta}")
+        .unwrap()
+        .progress_chars("## ")
+}
+
+impl Progress for Bars {
+    fn resolve_started(&self, slug: &str) {
+        if self.verbose {
+            self.multi.println(format!("Resolving {slug}")).ok();
+        }
+    }
+
+    fn file_started(&self, filename: &str, size: u64, resumed_from: u64) {
+        let mut bars = self.bars.lock().unwrap();
+        // a download that restarts keeps its bar
+        let bar = bars.entry(filename.to_string())
+            .or_insert_with(|| self.multi.add(ProgressBar::new(size).with_style(style())));
+
+        bar.set_length(size);
+        bar.set_position(resumed_from);
+        bar.reset_eta();
+        bar.set_message(match resumed_from {
+            0 => filename.to_string(),
+            _ => format!("{filename} (resumed)"),
+        });
+    }
+
+    fn file_progress(&self, filename: &str, downloaded: u64) {
+        self.with_bar(filename, |bar| bar.set_position(downloaded));
+    }
+
+    fn verify(&self, filename: &str) {
+        self.with_bar(filename, |bar| bar.set_message(format!("{filename} (verifying)")));
+    }
+
+    fn install(&self, filename: &str, _dest: &Path, cached: bool) {
+        if cached {
+            self.multi.println(format!("{filename} (from cache)")).ok();
+        } else {
+            self.with_bar(filename, |bar| bar.finish_with_message(filename.to_string()));
+        }
+    }
+}
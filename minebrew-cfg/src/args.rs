@@ -1,15 +1,13 @@
-use clap::{Arg, Command, ArgMatches};
-use super::valid_target_string;
+use clap::{Arg, ArgAction, Command, ArgMatches, ValueSource};
+use super::{exit_with_msg, parse_duration, VersionRange, Profile, Settings, Layer, Source};
 
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::time::Duration;
 
-// TODO: Think of a way to have one big configuration struct so that we can 
-// "merge" the command line arguments and the config file and just have one 
-// location for configuration
-
-// Custom parsing function for target string
+// Custom parsing function for target string, a version or a range of them
 fn parse_target(s: &str) -> Result<String, String> {
-    match valid_target_string(s) {
+    match s.parse::<VersionRange>() {
         Ok(_) => Ok(s.to_string()),
         Err(e) => Err(e.to_string()),
     }
@@ -19,7 +17,7 @@ fn arg_target<'a>() -> Arg<'a> {
     Arg::new("target")
         .short('t')
         .long("target")
-        .help("Minecraft version the mod(s) should be compatible with")
+        .help("Minecraft version or range of versions the mod(s) should be compatible with (ex: 1.19.2, 1.19.x, \">=1.19.2,<1.20\")")
         .takes_value(true)
         .value_parser(parse_target)
 }
@@ -27,95 +25,644 @@ fn arg_target<'a>() -> Arg<'a> {
 fn arg_mc_dir<'a>() -> Arg<'a> {
     Arg::new("mc_dir")
         .short('m')
-        .long("mc_dir")
+        .long("mc-dir")
+        .alias("mc_dir")
         .help("Path to .minecraft (minecraft for MacOS) folder")
         .takes_value(true)
         .value_parser(clap::value_parser!(PathBuf))
 }
 
+fn arg_loader<'a>() -> Arg<'a> {
+    Arg::new("loader")
+        .short('l')
+        .long("loader")
+        .help("Mod loader the mod(s) should be compatible with (fabric, forge, quilt...)")
+        .takes_value(true)
+        .value_parser(|s: &str| Ok::<_, String>(s.to_lowercase()))
+}
+
+fn arg_type<'a>() -> Arg<'a> {
+    Arg::new("type")
+        .long("type")
+        .help("Kind of project to look for")
+        .takes_value(true)
+        .value_parser(["mod", "resourcepack", "shader", "datapack"])
+        .default_value("mod")
+}
+
+fn arg_changelog<'a>() -> Arg<'a> {
+    Arg::new("changelog")
+        .long("changelog")
+        .help("Show what changed in every mod being upgraded before asking to install")
+        .action(ArgAction::SetTrue)
+}
+
+fn arg_profile<'a>() -> Arg<'a> {
+    Arg::new("profile")
+        .short('p')
+        .long("profile")
+        .help("Name of the profile from the config file to use")
+        .takes_value(true)
+        .global(true)
+}
+
+fn arg_instance<'a>() -> Arg<'a> {
+    Arg::new("instance")
+        .long("instance")
+        .help("Name of a launcher instance to use, see `mbrew instances`")
+        .takes_value(true)
+        .global(true)
+}
+
+fn arg_verbose<'a>() -> Arg<'a> {
+    Arg::new("verbose")
+        .short('v')
+        .long("verbose")
+        .help("Print more about what's happening, can be repeated")
+        .action(ArgAction::Count)
+        .global(true)
+}
+
+fn arg_quiet<'a>() -> Arg<'a> {
+    Arg::new("quiet")
+        .short('q')
+        .long("quiet")
+        .help("Only print errors and what was asked for")
+        .action(ArgAction::Count)
+        .global(true)
+}
+
+fn arg_format<'a>() -> Arg<'a> {
+    Arg::new("format")
+        .long("format")
+        .help("How to print lists of results")
+        .takes_value(true)
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .global(true)
+}
+
+fn arg_yes<'a>() -> Arg<'a> {
+    Arg::new("yes")
+        .short('y')
+        .long("yes")
+        .help("Answer yes to every prompt and pick the best search result")
+        .action(ArgAction::SetTrue)
+        .global(true)
+}
+
+fn arg_offline<'a>() -> Arg<'a> {
+    Arg::new("offline")
+        .long("offline")
+        .help("Only use what has been downloaded before, never touch the network")
+        .action(ArgAction::SetTrue)
+        .global(true)
+}
+
+fn arg_refresh<'a>() -> Arg<'a> {
+    Arg::new("refresh")
+        .long("refresh")
+        .help("Fetch everything from modrinth again instead of using cached responses")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("offline")
+        .global(true)
+}
+
+fn arg_api_url<'a>() -> Arg<'a> {
+    Arg::new("api_url")
+        .long("api-url")
+        .help("Modrinth compatible api to use instead of modrinth.com")
+        .takes_value(true)
+        .global(true)
+}
+
+fn arg_concurrency<'a>() -> Arg<'a> {
+    Arg::new("concurrency")
+        .long("concurrency")
+        .short('j')
+        .value_name("N")
+        .help("Most requests to make at once")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .global(true)
+}
+
+fn arg_timeout<'a>() -> Arg<'a> {
+    Arg::new("timeout")
+        .long("timeout")
+        .value_name("SECS")
+        .help("Seconds to wait for a response before trying again")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .global(true)
+}
+
+fn arg_cache_ttl<'a>() -> Arg<'a> {
+    Arg::new("cache_ttl")
+        .long("cache-ttl")
+        .value_name("DURATION")
+        .help("How long cached responses are used before asking modrinth if they changed (ex: 10m, 1h)")
+        .takes_value(true)
+        .value_parser(|s: &str| parse_duration(s).map(|_| s.to_string()))
+        .global(true)
+}
+
+fn arg_proxy<'a>() -> Arg<'a> {
+    Arg::new("proxy")
+        .long("proxy")
+        .value_name("URL")
+        .help("Send every request through this proxy")
+        .takes_value(true)
+        .global(true)
+}
+
+/// The options that decide the defaults of most of the others, --mc-dir,
+/// --profile and --instance, read before the arguments are properly parsed.
+/// Anything else wrong with the arguments is left for the real parse to report
+fn early_globals<T: Into<OsString> + Clone>(args: impl IntoIterator<Item = T>) -> (Option<PathBuf>, Option<String>, Option<String>) {
+    let matches = match app().ignore_errors(true).try_get_matches_from(args) {
+        Ok(matches) => matches,
+        // --help and --version end up here
+        Err(_) => return (None, None, None),
+    };
+
+    let leaf_matches = leaf(&matches);
+    let string = |id: &str| leaf_matches.try_get_one::<String>(id).ok().flatten().cloned();
+    (
+        leaf_matches.try_get_one::<PathBuf>("mc_dir").ok().flatten().cloned(),
+        string("profile"),
+        string("instance"),
+    )
+}
+
+/// The matches of the innermost subcommand, global options 
+/// are passed down so these hold every one of them
+fn leaf(matches: &ArgMatches) -> &ArgMatches {
+    match matches.subcommand() {
+        Some((_, sub_matches)) => leaf(sub_matches),
+        None => matches,
+    }
+}
+
+/// The global options that were typed on the command line,
+/// values clap filled in from their defaults are left out
+fn cli_layer(matches: &ArgMatches) -> Layer {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    Layer {
+        mc_dir: given("mc_dir").then(|| matches.get_one::<PathBuf>("mc_dir").unwrap().clone()),
+        target: given("target").then(|| matches.get_one::<String>("target").unwrap().clone()),
+        loader: given("loader").then(|| matches.get_one::<String>("loader").unwrap().clone()),
+        api_url: given("api_url").then(|| matches.get_one::<String>("api_url").unwrap().clone()),
+        concurrency: given("concurrency").then(|| *matches.get_one::<u64>("concurrency").unwrap()),
+        timeout: given("timeout").then(|| *matches.get_one::<u64>("timeout").unwrap()),
+        cache_ttl: given("cache_ttl").then(|| matches.get_one::<String>("cache_ttl").unwrap().clone()),
+        proxy: given("proxy").then(|| matches.get_one::<String>("proxy").unwrap().clone()),
+        ..Layer::default()
+    }
+}
+
+/// How lists of results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Global options that change how Minebrew talks to the user 
+/// rather than what it installs
+pub struct Globals {
+    // number of -v minus the number of -q
+    pub verbosity: i8,
+    pub format: OutputFormat,
+    // skip every prompt
+    pub yes: bool,
+    pub api_url: String,
+    pub concurrency: usize,
+    pub timeout: Duration,
+    pub token: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_certs: Vec<PathBuf>,
+    // "native" or "rustls"
+    pub tls: String,
+    // serve everything from the cache
+    pub offline: bool,
+    // skip cached responses
+    pub refresh: bool,
+    // how long a cached response is used before it's revalidated
+    pub cache_ttl: Duration,
+}
+
+impl Globals {
+    /// -q was passed, only print errors and results
+    pub fn quiet(&self) -> bool {
+        self.verbosity < 0
+    }
+
+    /// -v was passed, print extra details
+    pub fn verbose(&self) -> bool {
+        self.verbosity > 0
+    }
+}
+
 /// Struct to hold the arguments passed through the command line
 ///
 /// fields:
 /// - command: Subcommands
+/// - settings: Settings
+/// - globals: Globals
 pub struct Options {
     // The Subcommand enum which holds the struct 
     // with the arguments passed through
     pub command: Subcommands,
+
+    // every option merged from the config files, 
+    // environment and command line
+    pub settings: Settings,
+
+    // options every subcommand shares
+    pub globals: Globals,
+}
+
+/// Every subcommand and option Minebrew has, without the defaults
+/// that come from the settings
+fn app<'a>() -> Command<'a> {
+    Command::new("mbrew")
+    .name("Minebrew")
+    .author("The Brogrammers")
+    .version("0.0.2")
+    .about("A fast and hassle-free mod package manager for minecraft")
+    .subcommand_required(true)
+    .arg_required_else_help(true)
+    // global options, these can be used before or after any subcommand
+    .arg(arg_mc_dir().global(true))
+    .arg(arg_target().global(true))
+    .arg(arg_loader().global(true))
+    .arg(arg_profile())
+    .arg(arg_instance())
+    .arg(arg_verbose())
+    .arg(arg_quiet())
+    .arg(arg_format())
+    .arg(arg_yes())
+    .arg(arg_offline())
+    .arg(arg_refresh())
+    .arg(arg_api_url())
+    .arg(arg_concurrency())
+    .arg(arg_timeout())
+    .arg(arg_cache_ttl())
+    .arg(arg_proxy())
+    // install subcommand
+    .subcommand(
+        Command::new("install")
+            .about("Searches for and installs a mod")
+            .arg(
+                // install specific argument defined here
+                Arg::new("queries")
+                    .help("the mod(s) to installs separated by spaces, use slug@version for a specific version")
+                    .takes_value(true)
+                    .multiple_values(true)
+                    .required(true)
+            )
+            .arg(arg_type())
+            .arg(
+                Arg::new("world")
+                    .long("world")
+                    .help("Name of the world datapacks are installed into")
+                    .takes_value(true)
+            )
+            .arg(arg_changelog())
+            .arg(
+                Arg::new("forget")
+                    .long("forget")
+                    .help("Forget the search results picked for these queries before and ask again")
+                    .action(ArgAction::SetTrue)
+            )
+    )
+    // update subcommand
+    .subcommand(
+        Command::new("update")
+            .about("Upgrades installed mods to their newest versions, pinned mods stay put")
+            .arg(
+                Arg::new("queries")
+                    .help("slugs or project ids of the installed mods to update (default: all of them)")
+                    .takes_value(true)
+                    .multiple_values(true)
+            )
+            .arg(arg_type())
+            .arg(
+                Arg::new("world")
+                    .long("world")
+                    .help("Name of the world to update the datapacks of")
+                    .takes_value(true)
+            )
+            .arg(arg_changelog())
+    )
+    // search subcommand
+    .subcommand(
+        Command::new("search")
+            .about("Searches modrinth and lists the results")
+            .arg(
+                Arg::new("query")
+                    .help("what to search for")
+                    .takes_value(true)
+                    .multiple_values(true)
+                    .required(true)
+            )
+            .arg(arg_type())
+            .arg(
+                Arg::new("limit")
+                    .long("limit")
+                    .help("Maximum number of results to show")
+                    .takes_value(true)
+                    .value_parser(clap::value_parser!(u8).range(1..=100))
+                    .default_value("10")
+            )
+    )
+    // info subcommand
+    .subcommand(
+        Command::new("info")
+            .about("Shows everything modrinth knows about a mod")
+            .arg(
+                Arg::new("mod")
+                    .help("slug or project id of the mod")
+                    .takes_value(true)
+                    .required(true)
+            )
+    )
+    // changelog subcommand
+    .subcommand(
+        Command::new("changelog")
+            .about("Shows what changed between the installed version of a mod and the newest one")
+            .arg(
+                Arg::new("mod")
+                    .help("slug or project id of the mod")
+                    .takes_value(true)
+                    .required(true)
+            )
+            .arg(arg_type())
+    )
+    // audit subcommand
+    .subcommand(
+        Command::new("audit")
+            .about("Finds installed mods that were abandoned, pulled or stopped supporting the target")
+    )
+    // licenses subcommand
+    .subcommand(
+        Command::new("licenses")
+            .about("Lists the license of every installed mod, grouped by license")
+            .arg(
+                Arg::new("csv")
+                    .long("csv")
+                    .help("Print the list as csv, one row per mod")
+                    .action(ArgAction::SetTrue)
+            )
+    )
+    // pin subcommand
+    .subcommand(
+        Command::new("pin")
+            .about("Holds a mod at a specific version")
+            .arg(
+                Arg::new("mod")
+                    .help("slug or project id of the mod to pin")
+                    .takes_value(true)
+                    .required(true)
+            )
+            .arg(
+                Arg::new("version")
+                    .help("version id or version number to pin to (default: the installed version)")
+                    .takes_value(true)
+            )
+    )
+    // unpin subcommand
+    .subcommand(
+        Command::new("unpin")
+            .about("Allows a pinned mod to be updated again")
+            .arg(
+                Arg::new("mod")
+                    .help("slug or project id of the mod to unpin")
+                    .takes_value(true)
+                    .required(true)
+            )
+    )
+    // instances subcommand
+    .subcommand(
+        Command::new("instances")
+            .about("Lists the instances found in Prism Launcher, MultiMC and the Minecraft Launcher")
+    )
+    // profile subcommand
+    .subcommand(
+        Command::new("profile")
+            .about("Manages the profiles in the config file")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("create")
+                    .about("Creates a profile, unset options are copied from the current one")
+                    .arg(Arg::new("name").takes_value(true).required(true))
+                    .arg(
+                        Arg::new("side")
+                            .long("side")
+                            .help("Which side's mods to install")
+                            .takes_value(true)
+                            .value_parser(["client", "server"])
+                    )
+                    .arg(
+                        Arg::new("channel")
+                            .long("channel")
+                            .help("Least stable kind of version to install")
+                            .takes_value(true)
+                            .value_parser(["release", "beta", "alpha"])
+                    )
+            )
+            .subcommand(Command::new("list").about("Lists all profiles"))
+            .subcommand(
+                Command::new("switch")
+                    .about("Makes a profile the one used when --profile isn't passed")
+                    .arg(Arg::new("name").takes_value(true).required(true))
+            )
+            .subcommand(
+                Command::new("remove")
+                    .about("Removes a profile")
+                    .arg(Arg::new("name").takes_value(true).required(true))
+            )
+    )
+    // cache subcommand
+    .subcommand(
+        Command::new("cache")
+            .about("Manages the cache of downloaded files shared by every instance")
+            .subcommand_required(true)
+            .subcommand(Command::new("list").about("Lists every cached file"))
+            .subcommand(Command::new("size").about("Shows how much space the cache takes up"))
+            .subcommand(Command::new("clean").about("Removes every cached file"))
+            .subcommand(
+                Command::new("prune")
+                    .about("Removes cached files that haven't been used in a while")
+                    .arg(
+                        Arg::new("older_than")
+                            .long("older-than")
+                            .help("How long a file has to go unused to be removed (ex: 30d, 12h, 2w)")
+                            .takes_value(true)
+                            .value_parser(parse_duration)
+                            .default_value("30d")
+                    )
+            )
+    )
+    // config subcommand
+    .subcommand(
+        Command::new("config")
+            .about("Shows the configuration Minebrew is using")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("explain")
+                    .about("Lists every option's value and where it was set")
+            )
+    )
 }
 
 impl Options {
     pub fn parse() -> Self {
-        use super::ConfigFile;
-
-        // load config file for Options Defaults
-        let cfg_file = ConfigFile::load();
-        // unwrap them here so we dont have to later
-        let target = cfg_file.target.unwrap();
-        let mc_dir = cfg_file.mc_dir.unwrap();
-
-        // App Structure
-        let app = Command::new("mbrew")
-        .name("Minebrew")
-        .author("The Brogrammers")
-        .version("0.0.2")
-        .about("A fast and hassle-free mod package manager for minecraft")
-        .subcommand_required(true)
-        .arg_required_else_help(true)
-        // install subcommand
-        .subcommand(
-            Command::new("install")
-                .about("Searches for and installs a mod")
-                .arg(
-                    // install specific argument defined here
-                    Arg::new("queries")
-                        .help("the mod(s) to installs separated by spaces")
-                        .takes_value(true)
-                        .multiple_values(true)
-                        .required(true)
-                ) // Set default values for target and mc_dir 
-                  // arg_target and arg_mc_dir return args bc
-                  // they will be used outside of install
-                  // we add default values here for lifetime reasons
-                .arg( // --target option
-                        arg_target()
-                            .default_value(&target)
-                            .hide_default_value(true)
-                )
-                .arg( // --mc_dir options
-                        arg_mc_dir()
-                            .default_value_os(mc_dir.as_os_str())
-                            .hide_default_value(true)
-                )
-        );
+        // merge everything but the options typed on the command line, 
+        // these are the defaults for the global options
+        let (mc_dir, profile, instance) = early_globals(std::env::args_os());
+        let mut settings = Settings::resolve(Layer { mc_dir, ..Layer::default() }, profile, instance);
+        let target = settings.target.clone();
+        let mc_dir = settings.mc_dir.clone();
+        let loader = settings.loader.clone();
+        let api_url = settings.api_url.clone();
+        let concurrency = settings.concurrency.to_string();
+        let timeout = settings.timeout.to_string();
+
+        // the defaults come from the settings, added here for lifetime reasons
+        let mut app = app()
+            .mut_arg("mc_dir", |a| a.default_value_os(mc_dir.as_os_str()).hide_default_value(true))
+            .mut_arg("target", |a| a.default_value(&target).hide_default_value(true))
+            .mut_arg("api_url", |a| a.default_value(&api_url).hide_default_value(true))
+            .mut_arg("concurrency", |a| a.default_value(&concurrency).hide_default_value(true))
+            .mut_arg("timeout", |a| a.default_value(&timeout).hide_default_value(true));
+        // --loader only has a default if the config sets one
+        if let Some(l) = &loader {
+            app = app.mut_arg("loader", |a| a.default_value(l).hide_default_value(true));
+        }
 
         let mut matches = app.get_matches();
 
-        let (cmd, sub_matches) = matches.remove_subcommand().unwrap();
+        let leaf_matches = leaf(&matches);
+        let cli = cli_layer(leaf_matches);
+        if let Err((field, e)) = cli.validate() {
+            exit_with_msg(format!("Error with --{}: {e}", field.replace('_', "-")));
+        }
+        settings.apply(cli, Source::Cli);
 
-        match (cmd.as_str(), sub_matches) {
-            ("install", install_matches) => Self {
-                command: Subcommands::Install(InstallOpts::from(install_matches))
+        let globals = Globals {
+            verbosity: *leaf_matches.get_one::<u8>("verbose").unwrap() as i8 
+                - *leaf_matches.get_one::<u8>("quiet").unwrap() as i8,
+            format: match leaf_matches.get_one::<String>("format").unwrap().as_str() {
+                "json" => OutputFormat::Json,
+                _ => OutputFormat::Text,
             },
+            yes: *leaf_matches.get_one::<bool>("yes").unwrap(),
+            api_url: settings.api_url.clone(),
+            concurrency: settings.concurrency as usize,
+            timeout: Duration::from_secs(settings.timeout),
+            token: settings.token.clone(),
+            proxy: settings.proxy.clone(),
+            no_proxy: settings.no_proxy.clone(),
+            ca_certs: settings.ca_certs.clone(),
+            tls: settings.tls.clone(),
+            offline: *leaf_matches.get_one::<bool>("offline").unwrap(),
+            refresh: *leaf_matches.get_one::<bool>("refresh").unwrap(),
+            // every layer's value was checked by `validate`
+            cache_ttl: parse_duration(&settings.cache_ttl).unwrap(),
+        };
+
+        let (cmd, sub_matches) = matches.remove_subcommand().unwrap();
+
+        let command = match (cmd.as_str(), sub_matches) {
+            ("install", install_matches) => Subcommands::Install(InstallOpts {
+                side: settings.side.clone(),
+                channel: settings.channel.clone(),
+                allow_licenses: settings.allow_licenses.clone(),
+                deny_licenses: settings.deny_licenses.clone(),
+                forget: install_matches.get_flag("forget"),
+                ..InstallOpts::from(install_matches)
+            }),
+
+            ("update", update_matches) => Subcommands::Update(InstallOpts {
+                side: settings.side.clone(),
+                channel: settings.channel.clone(),
+                allow_licenses: settings.allow_licenses.clone(),
+                deny_licenses: settings.deny_licenses.clone(),
+                ..InstallOpts::from(update_matches)
+            }),
+
+            ("search", search_matches) => Subcommands::Search(SearchOpts::from(search_matches)),
+
+            ("info", info_matches) => Subcommands::Info(InfoOpts::from(info_matches)),
+
+            ("changelog", changelog_matches) => Subcommands::Changelog(ChangelogOpts {
+                channel: settings.channel.clone(),
+                ..ChangelogOpts::from(changelog_matches)
+            }),
+
+            ("audit", audit_matches) => Subcommands::Audit(AuditOpts::from(audit_matches)),
+
+            ("licenses", licenses_matches) => Subcommands::Licenses(LicensesOpts {
+                allow_licenses: settings.allow_licenses.clone(),
+                deny_licenses: settings.deny_licenses.clone(),
+                ..LicensesOpts::from(licenses_matches)
+            }),
+
+            ("pin", pin_matches) => Subcommands::Pin(PinOpts::from(pin_matches)),
+
+            ("unpin", unpin_matches) => Subcommands::Unpin(PinOpts::from(unpin_matches)),
+
+            ("instances", _) => Subcommands::Instances,
+
+            ("profile", profile_matches) => Subcommands::Profile(match ProfileOpts::from(profile_matches) {
+                // side and channel aren't global options, copy them from the current profile here
+                ProfileOpts::Create { name, mut profile } => {
+                    profile.side = profile.side.or_else(|| settings.side.clone());
+                    profile.channel = profile.channel.or_else(|| settings.channel.clone());
+                    ProfileOpts::Create { name, profile }
+                },
+                opts => opts,
+            }),
+
+            ("cache", cache_matches) => Subcommands::Cache(CacheOpts::from(cache_matches)),
+
+            ("config", config_matches) => Subcommands::Config(ConfigOpts::from(config_matches)),
 
             _ => unreachable!()
-        }
+        };
+
+        Self { command, settings, globals }
     }
 }
 
 // Our subcommands
 pub enum Subcommands {
     Install(InstallOpts),
-    Search,
+    Pin(PinOpts),
+    Unpin(PinOpts),
+    Profile(ProfileOpts),
+    Instances,
+    Search(SearchOpts),
+    Info(InfoOpts),
+    Changelog(ChangelogOpts),
+    Audit(AuditOpts),
+    Licenses(LicensesOpts),
+    Cache(CacheOpts),
     Remove,
-    Update,
-    Config, 
+    Update(InstallOpts),
+    Config(ConfigOpts), 
 }
 
 impl Subcommands {
-    /// "unwrap" the InstallOpts struct from enum
+    /// "unwrap" the InstallOpts struct from enum, 
+    /// update takes the same options as install
     pub fn install_opts(&mut self) -> Option<InstallOpts> {
         match self {
-            Subcommands::Install(i) => Some(std::mem::take(i)),
+            Subcommands::Install(i) | Subcommands::Update(i) => Some(std::mem::take(i)),
             _ => None,
         }
     }
@@ -129,14 +676,270 @@ pub struct InstallOpts {
     pub queries: Vec<String>,
     pub target: String,
     pub mc_dir: PathBuf,
+    pub loader: Option<String>,
+    // "mod", "resourcepack", "shader" or "datapack"
+    pub project_type: String,
+    // world to install datapacks into
+    pub world: Option<String>,
+    // show the changelogs of upgraded mods before installing
+    pub changelog: bool,
+    // ask again instead of reusing the search results picked before
+    pub forget: bool,
+    // these come from the profile rather than the command line
+    pub side: Option<String>,
+    pub channel: Option<String>,
+    // the license policy, from the config
+    pub allow_licenses: Option<Vec<String>>,
+    pub deny_licenses: Vec<String>,
 }
 
 impl From<ArgMatches> for InstallOpts {
     fn from(mut matches: ArgMatches) -> Self {
         Self {
-            queries: matches.remove_many("queries").unwrap().collect(),
+            // update is allowed to have none
+            queries: matches.remove_many("queries").map_or_else(Vec::new, |q| q.collect()),
+            target: matches.remove_one::<String>("target").unwrap(),
+            mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+            loader: matches.remove_one::<String>("loader"),
+            project_type: matches.remove_one::<String>("type").unwrap(),
+            world: matches.remove_one::<String>("world"),
+            changelog: matches.get_flag("changelog"),
+            forget: false,
+            side: None,
+            channel: None,
+            allow_licenses: None,
+            deny_licenses: Vec::new(),
+        }
+    }
+}
+
+// The search struct, holds the arguments 
+// passed through the search subcommand
+pub struct SearchOpts {
+    // all the words of the query joined by spaces
+    pub query: String,
+    pub target: String,
+    pub loader: Option<String>,
+    pub project_type: String,
+    pub limit: u8,
+}
+
+impl From<ArgMatches> for SearchOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            query: matches.remove_many::<String>("query").unwrap().collect::<Vec<_>>().join(" "),
             target: matches.remove_one::<String>("target").unwrap(),
+            loader: matches.remove_one::<String>("loader"),
+            project_type: matches.remove_one::<String>("type").unwrap(),
+            limit: matches.remove_one::<u8>("limit").unwrap(),
+        }
+    }
+}
+
+// The info struct, holds the arguments 
+// passed through the info subcommand
+pub struct InfoOpts {
+    // slug or project id of the mod
+    pub name: String,
+    // to find out whether it's installed
+    pub mc_dir: PathBuf,
+}
+
+impl From<ArgMatches> for InfoOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            name: matches.remove_one::<String>("mod").unwrap(),
+            mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+        }
+    }
+}
+
+// The changelog struct, holds the arguments 
+// passed through the changelog subcommand
+pub struct ChangelogOpts {
+    // slug or project id of the mod
+    pub name: String,
+    pub target: String,
+    pub mc_dir: PathBuf,
+    pub loader: Option<String>,
+    pub project_type: String,
+    // comes from the profile rather than the command line
+    pub channel: Option<String>,
+}
+
+impl From<ArgMatches> for ChangelogOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            name: matches.remove_one::<String>("mod").unwrap(),
+            target: matches.remove_one::<String>("target").unwrap(),
+            mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+            loader: matches.remove_one::<String>("loader"),
+            project_type: matches.remove_one::<String>("type").unwrap(),
+            channel: None,
+        }
+    }
+}
+
+// The audit struct, holds the arguments 
+// passed through the audit subcommand
+pub struct AuditOpts {
+    pub target: String,
+    pub mc_dir: PathBuf,
+    pub loader: Option<String>,
+}
+
+impl From<ArgMatches> for AuditOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            target: matches.remove_one::<String>("target").unwrap(),
+            mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+            loader: matches.remove_one::<String>("loader"),
+        }
+    }
+}
+
+// The licenses struct, holds the arguments 
+// passed through the licenses subcommand
+pub struct LicensesOpts {
+    pub mc_dir: PathBuf,
+    // print a csv table instead of the usual format
+    pub csv: bool,
+    // the license policy, from the config
+    pub allow_licenses: Option<Vec<String>>,
+    pub deny_licenses: Vec<String>,
+}
+
+impl From<ArgMatches> for LicensesOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+            csv: matches.get_flag("csv"),
+            allow_licenses: None,
+            deny_licenses: Vec::new(),
+        }
+    }
+}
+
+// The pin struct, holds the arguments passed through 
+// the pin and unpin subcommands
+pub struct PinOpts {
+    // slug or project id of the mod
+    pub name: String,
+    // version to pin to, always None for unpin
+    pub version: Option<String>,
+    pub mc_dir: PathBuf,
+}
+
+impl From<ArgMatches> for PinOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        Self {
+            name: matches.remove_one::<String>("mod").unwrap(),
+            version: matches.try_remove_one::<String>("version").ok().flatten(),
             mc_dir: matches.remove_one::<PathBuf>("mc_dir").unwrap()
         }
     }
 }
+
+// The profile enum, holds the arguments passed 
+// through the profile subcommands
+pub enum ProfileOpts {
+    Create { name: String, profile: Profile },
+    List,
+    Switch(String),
+    Remove(String),
+}
+
+impl From<ArgMatches> for ProfileOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        let (cmd, mut sub_matches) = matches.remove_subcommand().unwrap();
+        let name = sub_matches.try_remove_one::<String>("name").ok().flatten();
+
+        match cmd.as_str() {
+            "create" => ProfileOpts::Create {
+                name: name.unwrap(),
+                profile: Profile {
+                    mc_dir: sub_matches.remove_one::<PathBuf>("mc_dir").unwrap(),
+                    target: sub_matches.remove_one::<String>("target").unwrap(),
+                    loader: sub_matches.remove_one::<String>("loader"),
+                    side: sub_matches.remove_one::<String>("side"),
+                    channel: sub_matches.remove_one::<String>("channel"),
+                },
+            },
+            "list" => ProfileOpts::List,
+            "switch" => ProfileOpts::Switch(name.unwrap()),
+            "remove" => ProfileOpts::Remove(name.unwrap()),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// The config enum, holds the arguments passed 
+// through the config subcommands
+pub enum ConfigOpts {
+    Explain,
+}
+
+impl From<ArgMatches> for ConfigOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        let (cmd, _) = matches.remove_subcommand().unwrap();
+
+        match cmd.as_str() {
+            "explain" => ConfigOpts::Explain,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// The cache enum, holds the arguments passed 
+// through the cache subcommands
+pub enum CacheOpts {
+    List,
+    Size,
+    Clean,
+    Prune { older_than: Duration },
+}
+
+impl From<ArgMatches> for CacheOpts {
+    fn from(mut matches: ArgMatches) -> Self {
+        let (cmd, mut sub_matches) = matches.remove_subcommand().unwrap();
+
+        match cmd.as_str() {
+            "list" => CacheOpts::List,
+            "size" => CacheOpts::Size,
+            "clean" => CacheOpts::Clean,
+            "prune" => CacheOpts::Prune { 
+                older_than: sub_matches.remove_one::<Duration>("older_than").unwrap(),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use super::*;
+
+    #[test]
+    fn test_early_globals() {
+        let profile = |args: &[&str]| early_globals(args).1;
+        assert_eq!(profile(&["mbrew", "-pserver", "install", "sodium"]).as_deref(), Some("server"));
+        assert_eq!(profile(&["mbrew", "-p=server", "install", "sodium"]).as_deref(), Some("server"));
+        assert_eq!(profile(&["mbrew", "install", "sodium", "--profile", "server"]).as_deref(), Some("server"));
+
+        // a query that looks like an option isn't one
+        assert_eq!(profile(&["mbrew", "install", "--", "-pserver"]), None);
+
+        let (mc_dir, _, instance) = early_globals(["mbrew", "-m/srv", "--instance=Fabric", "audit"]);
+        assert_eq!(mc_dir, Some(PathBuf::from("/srv")));
+        assert_eq!(instance.as_deref(), Some("Fabric"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2 * 60 * 60 * 24)));
+        assert!(parse_duration("1y").is_err());
+        // too long to fit in a duration
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+}
@@ -1,80 +1,259 @@
-use super::{valid_target_string, get_mc_dir, exit_with_msg}; 
+use super::{exit_with_msg, ConfigError, Layer}; 
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-const DEFAULT_MC_VERSION: &str = "1.19";
-
-fn default_target() -> Option<String> { Some(DEFAULT_MC_VERSION.to_string()) }
-fn default_mc_dir() -> Option<PathBuf> { Some(get_mc_dir()) }
-
-#[derive(Deserialize)]
+// every top level option is optional, the defaults for 
+// anything left unset are filled in by the `settings` module
+#[derive(Default, Deserialize)]
 pub struct ConfigFile {
-    #[serde(default = "default_target")]
+    #[serde(default)]
     pub target: Option<String>,
 
-    #[serde(default = "default_mc_dir")]
+    #[serde(default)]
     pub mc_dir: Option<PathBuf>,
+
+    // no default loader, when it isn't set mods for any loader are allowed
+    #[serde(default)]
+    pub loader: Option<String>,
+
+    #[serde(default)]
+    pub side: Option<String>,
+
+    #[serde(default)]
+    pub channel: Option<String>,
+
+    // modrinth compatible api to use instead of modrinth.com
+    #[serde(default)]
+    pub api_url: Option<String>,
+
+    // most requests made to the api at once
+    #[serde(default)]
+    pub concurrency: Option<u64>,
+
+    // seconds to wait for the api to respond
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    // how long cached responses are used before they're revalidated, ex: "10m"
+    #[serde(default)]
+    pub cache_ttl: Option<String>,
+
+    // modrinth personal access token, keeping it in the token file is better
+    #[serde(default)]
+    pub token: Option<String>,
+
+    // proxy every request is sent through
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    // hosts that skip the proxy, ex: "localhost,.example.com"
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    // extra PEM files of root certificates to trust
+    #[serde(default)]
+    pub ca_certs: Option<Vec<PathBuf>>,
+
+    // "native" or "rustls"
+    #[serde(default)]
+    pub tls: Option<String>,
+
+    // when set, only mods with one of these licenses are installed
+    #[serde(default)]
+    pub allow_licenses: Option<Vec<String>>,
+
+    // mods with these licenses are never installed
+    #[serde(default)]
+    pub deny_licenses: Option<Vec<String>>,
+
+    // name of the profile used when --profile isn't passed
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
 }
 
-impl Default for ConfigFile {
-    fn default() -> Self {
-        Self { 
-            target: default_target(),
-            mc_dir: default_mc_dir(),
-        } 
+/// A named set of options for one minecraft instance,
+/// stored in the config file under `[profiles.<name>]`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub mc_dir: PathBuf,
+
+    pub target: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader: Option<String>,
+
+    // "client" or "server", which side's mods to install
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+
+    // "release", "beta" or "alpha", the least stable versions to install
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+}
+
+impl Profile {
+    /// Make sure every field of the profile holds a valid value,
+    /// returns the name of the bad field along with the error
+    pub fn validate(&self) -> Result<(), (&'static str, ConfigError)> {
+        Layer::from(self.clone()).validate()
     }
 }
 
 impl ConfigFile {
-    /// Function that finds and tries to load config file
-    /// ex location: "~/.config/mbrew/config.toml"
-    ///
-    ///  * If the config directory doesn't exist then it 
-    /// loads `ConfigFile::default()`
+    /// Location of the config file
+    /// ex: "~/.config/minebrew/config.toml"
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .expect("Unable to locate config directory...")
+            .join("minebrew")
+            .join("config.toml")
+    }
+
+    /// Location of the file holding just the user's modrinth token
+    /// ex: "~/.config/minebrew/token"
+    pub fn token_path() -> PathBuf {
+        Self::path().with_file_name("token")
+    }
+
+    /// The token kept in the token file, if there is one
+    pub fn read_token() -> Option<String> {
+        std::fs::read_to_string(Self::token_path()).ok()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+
+    /// Location of the config file shared by every user on the machine
+    /// ex: "/etc/minebrew/config.toml"
+    #[cfg(unix)]
+    pub fn system_path() -> PathBuf {
+        PathBuf::from("/etc/minebrew/config.toml")
+    }
+
+    /// Location of the config file shared by every user on the machine
+    /// ex: "C:\ProgramData\minebrew\config.toml"
+    #[cfg(windows)]
+    pub fn system_path() -> PathBuf {
+        std::env::var_os("ProgramData")
+            .map_or_else(|| PathBuf::from("C:\\ProgramData"), PathBuf::from)
+            .join("minebrew")
+            .join("config.toml")
+    }
+
+    /// Function that finds and tries to load the user's config file
+    /// ex location: "~/.config/minebrew/config.toml"
+    pub fn load() -> ConfigFile {
+        Self::read(&Self::path())
+    }
+
+    /// Function that tries to load a config file
     ///
-    /// * If the "minebrew" config directory exists but the 
-    /// "config.toml" file is not there then it loads `ConfigFile::default()`
+    /// * If the file or the directory it's in doesn't exist 
+    ///   then it loads `ConfigFile::default()` which sets nothing
     ///
     /// * If there was a different reason the config file couldn't 
-    /// be read then it returns an error
+    ///   be read then it returns an error
     ///
     /// * If parsing the config file fails return an error
     ///
-    /// * Checks if target field exists and if its valid, 
-    /// if it isnt then return an error
-    pub fn load() -> ConfigFile {
-        let mut config_dir = dirs::config_dir()              
-            .expect("Unable to locate config directory...");  // locate config dir
-       
-        config_dir.push("minebrew");                          // append "mbrew" to path so it
-
-        if !config_dir.exists() {                             // if the config directory does not exist
-            // std::fs::create_dir_all(&config_dir).ok();     // create it at default configuration directory
-            return ConfigFile::default();                 // Just use default Options for now
-        }
-
-        // attempt to read config.toml to string handling 
-        // specific errors by loading a default configuration
-
-        // /home/nels/.config/minebrew      config.toml --> /home/nels/.config/minebrew/config.toml
-
-        let config_file: ConfigFile = match std::fs::read_to_string(config_dir.join("config.toml")) { 
+    /// * Checks the top level fields are valid, 
+    ///   if they arent then return an error
+    ///
+    /// * Checks every profile's fields are valid,
+    ///   if one isnt then return an error
+    pub fn read(path: &Path) -> ConfigFile {
+        let config_file: ConfigFile = match std::fs::read_to_string(path) { 
             Ok(cfg) => {  
                 // if the file was read successfully try to parse it
                 toml::from_str(&cfg) // if can't be parsed print err msg and exit
-                    .unwrap_or_else(|e| exit_with_msg(format!("Error in config file: {}", e)))},
-            // Cool syntax I didn't know about 
+                    .unwrap_or_else(|e| exit_with_msg(format!("Error in config file \"{}\": {}", path.display(), e)))},
             // if the file isnt found then use default config otherwise return an error
             Err(err) if err.kind() == std::io::ErrorKind::NotFound => return ConfigFile::default(),
-            Err(err) => exit_with_msg(format!("Error reading \"config.toml\": {err}"))
+            Err(err) => exit_with_msg(format!("Error reading \"{}\": {err}", path.display()))
         };
 
-        // Takes &Option<String> and makes it an Option<&String> then we unwrap
-        match valid_target_string(config_file.target.as_ref().unwrap()) {
+        for (name, profile) in &config_file.profiles {
+            if let Err((field, e)) = profile.validate() {
+                exit_with_msg(format!("Error with field \"{field}\" of profile \"{name}\": {e}"))
+            }
+        }
+
+        match Layer::from(&config_file).validate() {
             Ok(_) => config_file,
-            Err(e) => exit_with_msg(format!("Error with field: {e}")) // invalid target string, exit program
+            Err((field, e)) => exit_with_msg(format!("Error with field \"{field}\": {e}")) // invalid field, exit program
         } 
     }
+
+    /// Read the config file as a plain toml table, edit it with `f`
+    /// and write it back. Editing the table rather than a `ConfigFile`
+    /// keeps defaults from being written out to the user's file
+    pub fn edit<T>(f: impl FnOnce(&mut toml::value::Table) -> Result<T, ConfigError>) -> Result<T, Box<dyn std::error::Error>> {
+        let path = Self::path();
+
+        let mut table: toml::value::Table = match std::fs::read_to_string(&path) {
+            Ok(cfg) => toml::from_str(&cfg)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::value::Table::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let out = f(&mut table)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // serializing it as a value puts plain values before tables like toml needs
+        std::fs::write(&path, toml::to_string_pretty(&toml::Value::Table(table))?)?;
+
+        Ok(out)
+    }
+
+    /// Add a profile to the config file, replacing any with the same name
+    pub fn create_profile(name: &str, profile: &Profile) -> Result<(), Box<dyn std::error::Error>> {
+        let value = toml::Value::try_from(profile)?;
+        Self::edit(|table| {
+            profiles_table(table).insert(name.to_string(), value);
+            Ok(())
+        })
+    }
+
+    /// Make `name` the profile used when --profile isn't passed
+    pub fn switch_profile(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Self::edit(|table| {
+            if !profiles_table(table).contains_key(name) {
+                return Err(ConfigError::ProfileNotFound(name.to_string()));
+            }
+            table.insert("profile".to_string(), toml::Value::String(name.to_string()));
+            Ok(())
+        })
+    }
+
+    /// Remove a profile from the config file, if it was the
+    /// active profile the top level options are used again
+    pub fn remove_profile(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Self::edit(|table| {
+            if profiles_table(table).remove(name).is_none() {
+                return Err(ConfigError::ProfileNotFound(name.to_string()));
+            }
+            if table.get("profile").and_then(toml::Value::as_str) == Some(name) {
+                table.remove("profile");
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Returns the `[profiles]` table of the config file, creating it if needed
+fn profiles_table(table: &mut toml::value::Table) -> &mut toml::value::Table {
+    let profiles = table.entry("profiles")
+        .or_insert_with(|| toml::Value::Table(toml::value::Table::new()));
+
+    if !profiles.is_table() {
+        *profiles = toml::Value::Table(toml::value::Table::new());
+    }
+    profiles.as_table_mut().unwrap()
 }
@@ -0,0 +1,325 @@
+use super::ConfigError;
+
+use std::cmp::Ordering;
+
+// Minecraft version numbers and ranges of them. Releases look like "1.19.2",
+// pre-releases and release candidates like "1.20-pre1" and "1.20-rc1" and
+// weekly snapshots like "23w13a". Releases, pre-releases and release
+// candidates all compare with each other, snapshots only compare with
+// other snapshots because their names don't say which release they lead to
+//
+// A range is either a single version, a wildcard like "1.19.x" or a comma
+// separated list of comparisons like ">=1.19.2,<1.20". Ranges are expanded
+// into the exact versions modrinth knows about before searching
+
+/// What kind of version a `GameVersion` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    Pre(u32),
+    Rc(u32),
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// releases, pre-releases and release candidates
+    Numbered { numbers: [u32; 3], stage: Stage },
+    /// weekly snapshots, ex: 23w13a is the first snapshot of week 13 of 2023
+    Snapshot { year: u32, week: u32, build: char },
+}
+
+/// A single minecraft version, keeps the text it was parsed from
+/// so "1.19" is displayed as "1.19" rather than "1.19.0"
+#[derive(Debug, Clone)]
+pub struct GameVersion {
+    text: String,
+    kind: Kind,
+}
+
+impl GameVersion {
+    /// Returns true for full releases, false for pre-releases,
+    /// release candidates and snapshots
+    pub fn is_release(&self) -> bool {
+        matches!(self.kind, Kind::Numbered { stage: Stage::Release, .. })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Parses the "1.19.2" part of a version, missing numbers are 0
+fn parse_numbers(s: &str) -> Option<[u32; 3]> {
+    let parts: Vec<&str> = s.split('.').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+
+    let mut numbers = [0; 3];
+    for (n, part) in numbers.iter_mut().zip(parts) {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        *n = part.parse().ok()?;
+    }
+    Some(numbers)
+}
+
+/// Parses the number after "pre" or "rc", it has to be there
+fn parse_stage_number(s: &str) -> Option<u32> {
+    match s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        true => None,
+        false => s.parse().ok(),
+    }
+}
+
+/// Parses a snapshot like "23w13a"
+fn parse_snapshot(s: &str) -> Option<Kind> {
+    let (year, rest) = s.split_once('w')?;
+    let build = rest.chars().last()?;
+    let week = &rest[..rest.len() - build.len_utf8()];
+
+    if year.len() != 2 || week.len() != 2 || !build.is_ascii_lowercase() {
+        return None;
+    }
+
+    Some(Kind::Snapshot {
+        year: parse_stage_number(year)?,
+        week: parse_stage_number(week)?,
+        build,
+    })
+}
+
+impl std::str::FromStr for GameVersion {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        // old pre-releases are named like "1.14 Pre-Release 5"
+        let (release, stage) = if let Some((release, n)) = s.split_once(" Pre-Release ") {
+            (release, parse_stage_number(n).map(Stage::Pre))
+        } else if let Some((release, stage)) = s.split_once('-') {
+            (release, match (stage.strip_prefix("pre"), stage.strip_prefix("rc")) {
+                (Some(n), _) => parse_stage_number(n).map(Stage::Pre),
+                (_, Some(n)) => parse_stage_number(n).map(Stage::Rc),
+                _ => None,
+            })
+        } else {
+            (s, Some(Stage::Release))
+        };
+
+        let kind = match (parse_numbers(release), stage) {
+            (Some(numbers), Some(stage)) => Kind::Numbered { numbers, stage },
+            _ => parse_snapshot(s).ok_or(ConfigError::TargetInvalidNum)?,
+        };
+
+        Ok(Self { text: s.to_string(), kind })
+    }
+}
+
+impl std::fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl PartialEq for GameVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl PartialOrd for GameVersion {
+    /// Snapshots and numbered versions can't be compared
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.kind, other.kind) {
+            (Kind::Numbered { numbers: a, stage: x }, Kind::Numbered { numbers: b, stage: y }) =>
+                Some((a, x).cmp(&(b, y))),
+            (Kind::Snapshot { year: a, week: b, build: c }, Kind::Snapshot { year: x, week: y, build: z }) =>
+                Some((a, b, c).cmp(&(x, y, z))),
+            _ => None,
+        }
+    }
+}
+
+/// One comparison in a range, ex: ">=1.19.2"
+#[derive(Debug, Clone)]
+struct Comparator {
+    op: Ordering,
+    or_equal: bool,
+    version: GameVersion,
+}
+
+impl Comparator {
+    fn matches(&self, version: &GameVersion) -> bool {
+        match version.partial_cmp(&self.version) {
+            Some(Ordering::Equal) => self.or_equal,
+            Some(ord) => ord == self.op,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Range {
+    Exact(GameVersion),
+    /// the leading numbers of a release, "1.19.x" is [1, 19]
+    Wildcard(Vec<u32>),
+    Compare(Vec<Comparator>),
+}
+
+/// A set of minecraft versions, parsed from a target like
+/// "1.19.2", "1.19.x" or ">=1.19.2,<1.20"
+#[derive(Debug, Clone)]
+pub struct VersionRange {
+    text: String,
+    range: Range,
+}
+
+impl VersionRange {
+    /// Returns the version if the range is a single version,
+    /// these don't need expanding
+    pub fn exact(&self) -> Option<&GameVersion> {
+        match &self.range {
+            Range::Exact(version) => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Returns true if `version` is in the range. Like cargo's version
+    /// requirements, pre-releases and snapshots are only matched by
+    /// comparisons against a pre-release or snapshot
+    pub fn matches(&self, version: &GameVersion) -> bool {
+        match &self.range {
+            Range::Exact(exact) => exact == version,
+            Range::Wildcard(prefix) => match version.kind {
+                Kind::Numbered { numbers, stage: Stage::Release } => numbers.starts_with(prefix),
+                _ => false,
+            },
+            Range::Compare(comparators) => {
+                (version.is_release() || comparators.iter().any(|c| !c.version.is_release()))
+                    && comparators.iter().all(|c| c.matches(version))
+            },
+        }
+    }
+
+    /// The versions out of `known` that are in the range, in the same order.
+    /// Versions that can't be parsed, like april fools snapshots, are skipped
+    pub fn expand<'a>(&self, known: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        known.into_iter()
+            .filter(|v| v.parse::<GameVersion>().is_ok_and(|v| self.matches(&v)))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Parses the "1.19" out of "1.19.x" or "1.19.*"
+fn parse_wildcard(s: &str) -> Option<Vec<u32>> {
+    let prefix = s.strip_suffix(".x").or_else(|| s.strip_suffix(".*"))?;
+    let numbers = prefix.split('.')
+        .map(|n| match n.is_empty() || !n.chars().all(|c| c.is_ascii_digit()) {
+            true => None,
+            false => n.parse().ok(),
+        })
+        .collect::<Option<Vec<u32>>>()?;
+
+    (1..=2).contains(&numbers.len()).then_some(numbers)
+}
+
+impl std::str::FromStr for Comparator {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        let (op, or_equal, version) = if let Some(v) = s.strip_prefix(">=") {
+            (Ordering::Greater, true, v)
+        } else if let Some(v) = s.strip_prefix("<=") {
+            (Ordering::Less, true, v)
+        } else if let Some(v) = s.strip_prefix('>') {
+            (Ordering::Greater, false, v)
+        } else if let Some(v) = s.strip_prefix('<') {
+            (Ordering::Less, false, v)
+        } else if let Some(v) = s.strip_prefix('=') {
+            (Ordering::Equal, true, v)
+        } else {
+            return Err(ConfigError::InvalidRange);
+        };
+
+        Ok(Self { op, or_equal, version: version.trim().parse()? })
+    }
+}
+
+impl std::str::FromStr for VersionRange {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, ConfigError> {
+        let s = s.trim();
+
+        let range = if let Some(prefix) = parse_wildcard(s) {
+            Range::Wildcard(prefix)
+        } else if s.starts_with(['<', '>', '=']) {
+            Range::Compare(s.split(',')
+                .map(|c| c.trim().parse())
+                .collect::<Result<_, _>>()?)
+        } else {
+            Range::Exact(s.parse()?)
+        };
+
+        Ok(Self { text: s.to_string(), range })
+    }
+}
+
+impl std::fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod game_version_tests {
+    use super::*;
+
+    fn v(s: &str) -> GameVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        for ok in ["1.19", "1.19.2", "1.20-pre1", "1.20.1-rc1", "1.14 Pre-Release 5", "23w13a"] {
+            assert!(ok.parse::<GameVersion>().is_ok(), "{ok}");
+        }
+        for bad in ["", "1", "1.", "1.2.3.4", "1.20-pre", "1.20-beta1", "23w13", "3D Shareware v1.34", "22w13oneblockatatime"] {
+            assert!(bad.parse::<GameVersion>().is_err(), "{bad}");
+        }
+        assert_eq!(v("1.19").to_string(), "1.19");
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(v("1.19") < v("1.19.1"));
+        assert!(v("1.9.4") < v("1.19"));
+        assert!(v("1.20-pre1") < v("1.20-pre2"));
+        assert!(v("1.20-pre7") < v("1.20-rc1"));
+        assert!(v("1.20-rc1") < v("1.20"));
+        assert!(v("1.19.4") < v("1.20-pre1"));
+        assert!(v("23w13a") < v("23w13b"));
+        assert!(v("22w45a") < v("23w03a"));
+        assert_eq!(v("1.19"), v("1.19.0"));
+        assert_eq!(v("23w13a").partial_cmp(&v("1.20")), None);
+    }
+
+    #[test]
+    fn test_expand() {
+        let known = ["1.20", "1.20-rc1", "23w13a", "1.19.4", "1.19.3", "1.19.2", "1.19.1", "1.19", "1.18.2"];
+        let expand = |range: &str| range.parse::<VersionRange>().unwrap().expand(known);
+
+        assert_eq!(expand("1.19.x"), ["1.19.4", "1.19.3", "1.19.2", "1.19.1", "1.19"]);
+        assert_eq!(expand(">=1.19.2,<1.20"), ["1.19.4", "1.19.3", "1.19.2"]);
+        assert_eq!(expand(">1.19.4"), ["1.20"]);
+        assert_eq!(expand(">=1.20-rc1"), ["1.20", "1.20-rc1"]);
+        assert_eq!(expand(">=23w01a"), ["23w13a"]);
+        assert_eq!(expand("1.19.2"), ["1.19.2"]);
+
+        assert!("1.19.2".parse::<VersionRange>().unwrap().exact().is_some());
+        assert!("~1.19".parse::<VersionRange>().is_err());
+        assert!(">=1.19,<".parse::<VersionRange>().is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use super::GameVersion;
+use super::instances::parse_version_id;
+
+use serde::Deserialize;
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// Works out which minecraft version and loader a directory is set up for
+// by looking at what's inside it. Each source of information is tried in
+// turn and the first one that gives a single answer is used, if they're
+// all ambiguous nothing is inferred and the config file is used instead
+//
+// 1. the official launcher's most recently used profile for the directory
+// 2. the installed versions in "versions/"
+// 3. a dedicated server's jar and libraries
+
+/// Library folders that tell us which loader a server runs
+const SERVER_LOADERS: [(&str, &str); 4] = [
+    ("libraries/net/fabricmc/fabric-loader", "fabric"),
+    ("libraries/org/quiltmc/quilt-loader", "quilt"),
+    ("libraries/net/neoforged", "neoforge"),
+    ("libraries/net/minecraftforge/forge", "forge"),
+];
+
+/// What could be worked out about a minecraft directory
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Inferred {
+    pub target: Option<String>,
+    pub loader: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LauncherProfiles {
+    profiles: HashMap<String, LauncherProfile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LauncherProfile {
+    game_dir: Option<PathBuf>,
+    last_version_id: Option<String>,
+    #[serde(default)]
+    last_used: String,
+}
+
+/// The parts of a version json in "versions/<id>/<id>.json" we need
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionJson {
+    id: String,
+    inherits_from: Option<String>,
+}
+
+/// Inspect a minecraft directory to work out its version and loader
+pub fn infer(mc_dir: &Path) -> Inferred {
+    let sources = [
+        from_launcher_profile(mc_dir),
+        from_versions_dir(mc_dir),
+        from_server(mc_dir),
+    ];
+
+    let target = sources.iter().find_map(|s| s.target.clone());
+    // only trust a loader from a source that agrees on the version
+    let loader = sources.into_iter()
+        .filter(|s| s.target.is_none() || s.target == target)
+        .find_map(|s| s.loader);
+
+    Inferred { target, loader }
+}
+
+/// Returns the single item of a set, or `None` if there are several
+fn only<T>(set: BTreeSet<T>) -> Option<T> {
+    match set.len() {
+        1 => set.into_iter().next(),
+        _ => None,
+    }
+}
+
+/// The version of the launcher profile that last played in this directory
+fn from_launcher_profile(mc_dir: &Path) -> Inferred {
+    let profiles = match std::fs::read_to_string(mc_dir.join("launcher_profiles.json")).ok()
+        .and_then(|json| serde_json::from_str::<LauncherProfiles>(&json).ok()) {
+        Some(profiles) => profiles.profiles,
+        None => return Inferred::default(),
+    };
+
+    // timestamps are ISO 8601 so they sort as strings
+    let (target, loader) = profiles.into_values()
+        .filter(|p| p.game_dir.as_deref().is_none_or(|dir| dir == mc_dir))
+        .max_by(|a, b| a.last_used.cmp(&b.last_used))
+        .and_then(|p| p.last_version_id)
+        .map(|id| parse_version_id(&id))
+        .unwrap_or_default();
+
+    Inferred { target, loader }
+}
+
+/// The versions installed in "versions/", only useful if they all
+/// agree on the minecraft version
+fn from_versions_dir(mc_dir: &Path) -> Inferred {
+    let entries = match std::fs::read_dir(mc_dir.join("versions")) {
+        Ok(entries) => entries,
+        Err(_) => return Inferred::default(),
+    };
+
+    let mut targets = BTreeSet::new();
+    let mut loaders = BTreeSet::new();
+
+    for dir in entries.filter_map(Result::ok).map(|e| e.path()) {
+        let id = dir.file_name().unwrap().to_string_lossy().to_string();
+
+        // loader versions say which minecraft version they're for
+        let json = std::fs::read_to_string(dir.join(format!("{id}.json"))).ok()
+            .and_then(|json| serde_json::from_str::<VersionJson>(&json).ok());
+
+        let (target, loader) = match json {
+            Some(VersionJson { id, inherits_from: Some(parent) }) => (Some(parent), parse_version_id(&id).1),
+            Some(VersionJson { id, inherits_from: None }) => parse_version_id(&id),
+            None => parse_version_id(&id),
+        };
+
+        targets.extend(target.filter(|t| t.parse::<GameVersion>().is_ok()));
+        loaders.extend(loader);
+    }
+
+    // a loader is only meaningful alongside the version it was installed for
+    match only(targets) {
+        Some(target) => Inferred { target: Some(target), loader: only(loaders) },
+        None => Inferred::default(),
+    }
+}
+
+/// The version of a dedicated server, read from the version.json inside
+/// its jar or the folder its libraries were installed to
+fn from_server(mc_dir: &Path) -> Inferred {
+    let loader = SERVER_LOADERS.iter()
+        .find(|(dir, _)| mc_dir.join(dir).is_dir())
+        .map(|(_, loader)| loader.to_string());
+
+    // forge and neoforge servers keep the vanilla server in their libraries
+    let libraries = std::fs::read_dir(mc_dir.join("libraries/net/minecraft/server")).ok()
+        .map(|entries| entries.filter_map(Result::ok)
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|v| v.parse::<GameVersion>().is_ok())
+            .collect::<BTreeSet<_>>())
+        .and_then(only);
+
+    let target = libraries.or_else(|| {
+        let jars = std::fs::read_dir(mc_dir).ok()?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "jar"));
+
+        only(jars.filter_map(|jar| jar_version(&jar)).collect())
+    });
+
+    Inferred { target, loader }
+}
+
+/// Reads the minecraft version out of the version.json that
+/// vanilla server jars (1.14 and newer) are shipped with
+fn jar_version(jar: &Path) -> Option<String> {
+    let file = std::fs::File::open(jar).ok()?;
+    let mut archive = zip::ZipArchive::new(file).ok()?;
+    let mut entry = archive.by_name("version.json").ok()?;
+
+    let mut json = String::new();
+    entry.read_to_string(&mut json).ok()?;
+
+    let version: VersionJson = serde_json::from_str(&json).ok()?;
+    Some(version.id).filter(|id| id.parse::<GameVersion>().is_ok())
+}
+
+#[cfg(test)]
+mod infer_tests {
+    use super::*;
+
+    /// Makes an empty directory to build a fake minecraft directory in
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minebrew-infer-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn add_version(mc_dir: &Path, id: &str, json: Option<&str>) {
+        let dir = mc_dir.join("versions").join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        if let Some(json) = json {
+            std::fs::write(dir.join(format!("{id}.json")), json).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_versions_dir() {
+        let mc_dir = temp_dir("versions");
+        add_version(&mc_dir, "1.19.2", None);
+        add_version(&mc_dir, "fabric-loader-0.14.9-1.19.2",
+            Some(r#"{ "id": "fabric-loader-0.14.9-1.19.2", "inheritsFrom": "1.19.2" }"#));
+
+        let inferred = infer(&mc_dir);
+        assert_eq!(inferred.target.as_deref(), Some("1.19.2"));
+        assert_eq!(inferred.loader.as_deref(), Some("fabric"));
+
+        // a second minecraft version makes it ambiguous
+        add_version(&mc_dir, "1.18.2", None);
+        assert_eq!(infer(&mc_dir), Inferred::default());
+
+        std::fs::remove_dir_all(&mc_dir).ok();
+    }
+
+    #[test]
+    fn test_launcher_profile() {
+        let mc_dir = temp_dir("launcher");
+        add_version(&mc_dir, "1.19.2", None);
+        add_version(&mc_dir, "1.18.2", None);
+        add_version(&mc_dir, "1.18.2-forge-40.1.0", None);
+
+        std::fs::write(mc_dir.join("launcher_profiles.json"), r#"{ "profiles": {
+            "a": { "lastVersionId": "1.19.2", "lastUsed": "2022-06-01T10:00:00.000Z" },
+            "b": { "lastVersionId": "1.18.2-forge-40.1.0", "lastUsed": "2022-07-01T10:00:00.000Z" },
+            "c": { "lastVersionId": "1.17.1", "lastUsed": "2022-08-01T10:00:00.000Z", "gameDir": "/elsewhere" }
+        } }"#).unwrap();
+
+        let inferred = infer(&mc_dir);
+        assert_eq!(inferred.target.as_deref(), Some("1.18.2"));
+        assert_eq!(inferred.loader.as_deref(), Some("forge"));
+
+        std::fs::remove_dir_all(&mc_dir).ok();
+    }
+
+    #[test]
+    fn test_server_libraries() {
+        let mc_dir = temp_dir("server");
+        std::fs::create_dir_all(mc_dir.join("libraries/net/minecraft/server/1.19.2")).unwrap();
+        std::fs::create_dir_all(mc_dir.join("libraries/net/minecraftforge/forge/1.19.2-43.1.1")).unwrap();
+
+        let inferred = infer(&mc_dir);
+        assert_eq!(inferred.target.as_deref(), Some("1.19.2"));
+        assert_eq!(inferred.loader.as_deref(), Some("forge"));
+        std::fs::remove_dir_all(&mc_dir).ok();
+
+        // snapshots and pre-releases are found too
+        let mc_dir = temp_dir("server-snapshot");
+        std::fs::create_dir_all(mc_dir.join("libraries/net/minecraft/server/1.20-pre1")).unwrap();
+        assert_eq!(infer(&mc_dir).target.as_deref(), Some("1.20-pre1"));
+
+        std::fs::remove_dir_all(&mc_dir).ok();
+    }
+}
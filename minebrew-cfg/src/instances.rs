@@ -0,0 +1,260 @@
+use super::{valid_target_string, get_mc_dir, GameVersion};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Finds minecraft instances created by launchers so they can be targeted
+// by name instead of by path. Prism Launcher and MultiMC (and its forks)
+// keep every instance in its own folder under "instances/", the official
+// launcher keeps a list of profiles in "launcher_profiles.json"
+
+/// Data directory names used by MultiMC style launchers
+const MMC_LAUNCHERS: [(&str, Launcher); 4] = [
+    ("PrismLauncher", Launcher::Prism),
+    ("PolyMC", Launcher::Prism),
+    ("multimc", Launcher::MultiMc),
+    ("MultiMC", Launcher::MultiMc),
+];
+
+/// mmc-pack.json component uids of the loaders we know about
+const LOADER_UIDS: [(&str, &str); 4] = [
+    ("net.fabricmc.fabric-loader", "fabric"),
+    ("org.quiltmc.quilt-loader", "quilt"),
+    ("net.minecraftforge", "forge"),
+    ("net.neoforged", "neoforge"),
+];
+
+/// The launcher an instance belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Launcher {
+    Prism,
+    MultiMc,
+    Official,
+}
+
+impl std::fmt::Display for Launcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Launcher::Prism => write!(f, "Prism Launcher"),
+            Launcher::MultiMc => write!(f, "MultiMC"),
+            Launcher::Official => write!(f, "Minecraft Launcher"),
+        }
+    }
+}
+
+/// A minecraft instance found in one of the launchers
+#[derive(Debug, Clone, Serialize)]
+pub struct Instance {
+    pub name: String,
+    pub launcher: Launcher,
+    pub mc_dir: PathBuf,
+    // only known if the launcher records it
+    pub target: Option<String>,
+    pub loader: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MmcPack {
+    components: Vec<MmcComponent>,
+}
+
+#[derive(Deserialize)]
+struct MmcComponent {
+    uid: String,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LauncherProfiles {
+    profiles: HashMap<String, LauncherProfile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LauncherProfile {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    game_dir: Option<PathBuf>,
+    last_version_id: Option<String>,
+}
+
+/// Finds the instances of every launcher installed
+pub fn discover() -> Vec<Instance> {
+    let mut instances = Vec::new();
+
+    if let Some(data_dir) = dirs::data_dir() {
+        for (dir, launcher) in MMC_LAUNCHERS {
+            instances.extend(mmc_instances(&data_dir.join(dir), launcher));
+        }
+    }
+
+    instances.extend(official_instances(&get_mc_dir()));
+    instances
+}
+
+/// Finds the instance called `name`, ignoring case
+pub fn find(name: &str) -> Result<Instance, String> {
+    let mut found: Vec<Instance> = discover().into_iter()
+        .filter(|i| i.name.eq_ignore_ascii_case(name))
+        .collect();
+
+    match found.len() {
+        0 => Err(format!("no instance named \"{name}\", run `mbrew instances` to list them")),
+        1 => Ok(found.remove(0)),
+        _ => Err(format!("more than one instance is named \"{name}\" ({})",
+            found.iter().map(|i| i.launcher.to_string()).collect::<Vec<_>>().join(", "))),
+    }
+}
+
+/// Reads every instance in a MultiMC style launcher's data directory
+pub fn mmc_instances(root: &Path, launcher: Launcher) -> Vec<Instance> {
+    let entries = match std::fs::read_dir(root.join("instances")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries.filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter_map(|dir| {
+            let cfg = std::fs::read_to_string(dir.join("instance.cfg")).ok()?;
+            let name = cfg.lines()
+                .find_map(|l| l.trim().strip_prefix("name="))
+                .map(str::to_string)
+                .unwrap_or_else(|| dir.file_name().unwrap().to_string_lossy().to_string());
+
+            let (target, loader) = std::fs::read_to_string(dir.join("mmc-pack.json")).ok()
+                .and_then(|json| serde_json::from_str::<MmcPack>(&json).ok())
+                .map(|pack| pack_versions(&pack))
+                .unwrap_or_default();
+
+            // newer launchers use "minecraft", older ones ".minecraft"
+            let mc_dir = if dir.join("minecraft").is_dir() {
+                dir.join("minecraft")
+            } else {
+                dir.join(".minecraft")
+            };
+
+            Some(Instance { name, launcher, mc_dir, target, loader })
+        })
+        .collect()
+}
+
+/// Pulls the minecraft version and loader out of an mmc-pack.json
+fn pack_versions(pack: &MmcPack) -> (Option<String>, Option<String>) {
+    let target = pack.components.iter()
+        .find(|c| c.uid == "net.minecraft")
+        .and_then(|c| c.version.clone())
+        .filter(|v| v.parse::<GameVersion>().is_ok());
+
+    let loader = pack.components.iter()
+        .find_map(|c| LOADER_UIDS.iter().find(|(uid, _)| c.uid == *uid))
+        .map(|(_, loader)| loader.to_string());
+
+    (target, loader)
+}
+
+/// Reads the profiles of the official launcher, profiles without
+/// a custom game directory share the default minecraft directory
+pub fn official_instances(mc_dir: &Path) -> Vec<Instance> {
+    let profiles = match std::fs::read_to_string(mc_dir.join("launcher_profiles.json")).ok()
+        .and_then(|json| serde_json::from_str::<LauncherProfiles>(&json).ok()) {
+        Some(profiles) => profiles.profiles,
+        None => return Vec::new(),
+    };
+
+    profiles.into_values()
+        .map(|p| {
+            let (target, loader) = p.last_version_id.as_deref()
+                .map(parse_version_id)
+                .unwrap_or_default();
+
+            let name = match (p.name.is_empty(), p.kind.as_str()) {
+                (false, _) => p.name,
+                (true, "latest-release") => "Latest release".to_string(),
+                (true, "latest-snapshot") => "Latest snapshot".to_string(),
+                (true, kind) => kind.to_string(),
+            };
+
+            Instance {
+                name,
+                launcher: Launcher::Official,
+                mc_dir: p.game_dir.unwrap_or_else(|| mc_dir.to_path_buf()),
+                target,
+                loader,
+            }
+        })
+        .collect()
+}
+
+/// Splits the id of an installed version, the name of its folder in
+/// "versions/", into the minecraft version and the loader
+///
+/// ex: "1.19.2", "23w13a", "fabric-loader-0.14.9-1.19.2", "1.19.2-forge-43.1.1"
+pub fn parse_version_id(id: &str) -> (Option<String>, Option<String>) {
+    // vanilla releases, pre-releases and snapshots
+    if id.parse::<GameVersion>().is_ok() {
+        return (Some(id.to_string()), None);
+    }
+
+    let loader = ["fabric", "quilt", "neoforge", "forge"].into_iter()
+        .find(|l| id.to_lowercase().contains(l));
+
+    let mut versions = id.split('-').filter(|part| valid_target_string(part).is_ok());
+
+    let target = match loader {
+        // "fabric-loader-<loader version>-<minecraft version>"
+        Some("fabric") | Some("quilt") => versions.next_back().map(str::to_string),
+        // "neoforge-20.4.80" is for minecraft 1.20.4
+        Some("neoforge") if id.starts_with("neoforge-") => versions.next().and_then(|v| {
+            let mut nums = v.split('.');
+            match (nums.next(), nums.next()) {
+                (Some(major), Some("0")) => Some(format!("1.{major}")),
+                (Some(major), Some(minor)) => Some(format!("1.{major}.{minor}")),
+                _ => None,
+            }
+        }),
+        // "<minecraft version>-forge-<loader version>"
+        _ => versions.next().map(str::to_string),
+    };
+
+    (target, loader.map(str::to_string))
+}
+
+#[cfg(test)]
+mod instances_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version_id() {
+        let owned = |t: &str, l: Option<&str>| (Some(t.to_string()), l.map(str::to_string));
+
+        assert_eq!(parse_version_id("1.19.2"), owned("1.19.2", None));
+        assert_eq!(parse_version_id("1.20-pre1"), owned("1.20-pre1", None));
+        assert_eq!(parse_version_id("23w13a"), owned("23w13a", None));
+        assert_eq!(parse_version_id("fabric-loader-0.14.9-1.19.2"), owned("1.19.2", Some("fabric")));
+        assert_eq!(parse_version_id("quilt-loader-0.17.1-1.19.2"), owned("1.19.2", Some("quilt")));
+        assert_eq!(parse_version_id("1.18.2-forge-40.1.0"), owned("1.18.2", Some("forge")));
+        assert_eq!(parse_version_id("neoforge-20.4.80"), owned("1.20.4", Some("neoforge")));
+        assert_eq!(parse_version_id("neoforge-20.2.86"), owned("1.20.2", Some("neoforge")));
+        assert_eq!(parse_version_id("latest-release"), (None, None));
+    }
+
+    #[test]
+    fn test_pack_versions() {
+        let pack: MmcPack = serde_json::from_str(r#"{
+            "components": [
+                { "uid": "org.lwjgl3", "version": "3.3.1" },
+                { "uid": "net.minecraft", "version": "1.19.2" },
+                { "uid": "net.fabricmc.intermediary", "version": "1.19.2" },
+                { "uid": "net.fabricmc.fabric-loader", "version": "0.14.9" }
+            ]
+        }"#).unwrap();
+
+        assert_eq!(pack_versions(&pack), (Some("1.19.2".to_string()), Some("fabric".to_string())));
+    }
+}
@@ -1,17 +1,31 @@
-/// Configuration Goal: Load a default version of 
+//! Configuration Goal: Load a default version of 
 
 mod args;
 mod config_file;
+pub mod game_version;
+pub mod infer;
+pub mod instances;
+pub mod settings;
 
-use config_file::ConfigFile;
-pub use args::{ Options, Subcommands };
+pub use config_file::{ConfigFile, Profile};
+pub use game_version::{GameVersion, VersionRange};
+pub use settings::{Settings, Layer, Source};
+pub use args::{ Options, Globals, OutputFormat, Subcommands, InstallOpts, PinOpts, SearchOpts, InfoOpts, ChangelogOpts, AuditOpts, LicensesOpts, ProfileOpts, CacheOpts, ConfigOpts };
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum ConfigError {
     TargetInvalidNum,
     TargetInvalidChars,
+    InvalidRange,
+    InvalidUrl,
+    InvalidProxy(&'static [&'static str]),
+    InvalidDuration(String),
+    NotPositive,
+    InvalidChoice(&'static [&'static str]),
+    ProfileNotFound(String),
 }
 
 impl std::error::Error for ConfigError {} 
@@ -24,17 +38,56 @@ impl std::fmt::Display for ConfigError {
 
             ConfigError::TargetInvalidChars => 
                 write!(f, "contains invalid sequence of characters"),
+
+            ConfigError::InvalidRange => 
+                write!(f, "isn't a valid version range (ex: 1.19.x, >=1.19.2,<1.20)"),
+
+            ConfigError::InvalidUrl => 
+                write!(f, "must start with http:// or https://"),
+
+            ConfigError::InvalidProxy(schemes) => 
+                write!(f, "must start with {}", schemes.join(", ")),
+
+            ConfigError::InvalidDuration(msg) => 
+                write!(f, "{msg}"),
+
+            ConfigError::NotPositive => 
+                write!(f, "must be a whole number above 0"),
+
+            ConfigError::InvalidChoice(choices) => 
+                write!(f, "must be one of {}", choices.join(", ")),
+
+            ConfigError::ProfileNotFound(name) => 
+                write!(f, "no profile named \"{name}\""),
         }
     }
 }
 
+/// Parse a length of time like "30d" or "12h", a number on its own is days
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
+    let unit_at = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(unit_at);
+    let invalid = || format!("\"{s}\" should be a number followed by s, m, h, d or w");
+    let num: u64 = num.parse().map_err(|_| invalid())?;
+
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" | "" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(format!("unknown unit \"{unit}\", use s, m, h, d or w")),
+    };
+    num.checked_mul(secs).map(Duration::from_secs).ok_or_else(invalid)
+}
+
 /// Convience functino for ending the program with an error message
-pub fn exit_with_msg<'a, T: AsRef<str> + std::fmt::Display>(msg: T) -> ! {
+pub fn exit_with_msg<T: AsRef<str> + std::fmt::Display>(msg: T) -> ! {
     eprintln!("{msg}");
     std::process::exit(1);
 }
 
-/// loads the Configuration Options
+// loads the Configuration Options
 // Parses command line arguments and then attempts to read the 
 // config file into a struct exiting the program if there is an 
 // error reading the file
@@ -0,0 +1,531 @@
+use super::{exit_with_msg, get_mc_dir, parse_duration, infer, instances, ConfigError, ConfigFile, Profile, VersionRange};
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// Merges every place an option can be set into one `Settings` struct,
+// later layers override earlier ones:
+//
+// 1. built in defaults, the token file "~/.config/minebrew/token" and
+//    the usual HTTPS_PROXY and NO_PROXY variables
+// 2. the system config, "/etc/minebrew/config.toml"
+// 3. the user config, "~/.config/minebrew/config.toml", then its active profile
+// 4. the launcher instance, or what's worked out from the minecraft directory
+// 5. MBREW_* environment variables
+// 6. the command line
+//
+// Where each value came from is kept so `mbrew config explain` can show it
+
+const DEFAULT_MC_VERSION: &str = "1.19";
+
+const DEFAULT_API_URL: &str = "https://api.modrinth.com/v2";
+
+const DEFAULT_CONCURRENCY: u64 = 8;
+
+/// Seconds to wait for the api to respond
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// How long cached responses are used before they're revalidated
+const DEFAULT_CACHE_TTL: &str = "10m";
+
+/// Allowed values for side
+const SIDES: [&str; 2] = ["client", "server"];
+
+/// Allowed values for release channel
+const CHANNELS: [&str; 3] = ["release", "beta", "alpha"];
+
+/// Allowed values for tls
+const TLS_BACKENDS: [&str; 2] = ["native", "rustls"];
+
+/// Schemes a proxy url can start with
+const PROXY_SCHEMES: [&str; 4] = ["http://", "https://", "socks5://", "socks5h://"];
+
+/// Where the value of a setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    TokenFile,
+    SystemConfig,
+    UserConfig,
+    Profile(String),
+    Instance(String),
+    Inferred,
+    // a variable other programs use too, like HTTPS_PROXY
+    StandardEnv(&'static str),
+    Env,
+    Cli,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::TokenFile => write!(f, "token file ({})", ConfigFile::token_path().display()),
+            Source::SystemConfig => write!(f, "system config ({})", ConfigFile::system_path().display()),
+            Source::UserConfig => write!(f, "user config ({})", ConfigFile::path().display()),
+            Source::Profile(name) => write!(f, "profile \"{name}\""),
+            Source::Instance(name) => write!(f, "instance \"{name}\""),
+            Source::Inferred => write!(f, "minecraft directory"),
+            Source::StandardEnv(name) => write!(f, "environment ({name})"),
+            Source::Env => write!(f, "environment"),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Split a comma separated list from an environment variable
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|l| !l.is_empty()).map(str::to_string).collect()
+}
+
+/// The options set by one layer, `None` leaves the value
+/// from the layers below untouched
+#[derive(Debug, Default, Clone)]
+pub struct Layer {
+    pub mc_dir: Option<PathBuf>,
+    pub target: Option<String>,
+    pub loader: Option<String>,
+    pub side: Option<String>,
+    pub channel: Option<String>,
+    pub api_url: Option<String>,
+    pub concurrency: Option<u64>,
+    pub timeout: Option<u64>,
+    pub cache_ttl: Option<String>,
+    pub token: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_certs: Option<Vec<PathBuf>>,
+    pub tls: Option<String>,
+    pub allow_licenses: Option<Vec<String>>,
+    pub deny_licenses: Option<Vec<String>>,
+}
+
+impl Layer {
+    /// Reads the MBREW_* variables through `var`,
+    /// empty variables count as unset
+    pub fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).filter(|v| !v.is_empty());
+        Self {
+            mc_dir: var("MBREW_MC_DIR").map(PathBuf::from),
+            target: var("MBREW_TARGET"),
+            loader: var("MBREW_LOADER").map(|l| l.to_lowercase()),
+            side: var("MBREW_SIDE"),
+            channel: var("MBREW_CHANNEL"),
+            api_url: var("MBREW_API_URL"),
+            // anything that isn't a number is caught by `validate` as 0
+            concurrency: var("MBREW_CONCURRENCY").map(|n| n.parse().unwrap_or(0)),
+            timeout: var("MBREW_TIMEOUT").map(|n| n.parse().unwrap_or(0)),
+            cache_ttl: var("MBREW_CACHE_TTL"),
+            token: var("MBREW_TOKEN"),
+            proxy: var("MBREW_PROXY"),
+            no_proxy: var("MBREW_NO_PROXY"),
+            ca_certs: var("MBREW_CA_CERTS").map(|paths| std::env::split_paths(&paths).collect()),
+            tls: var("MBREW_TLS"),
+            allow_licenses: var("MBREW_ALLOW_LICENSES").map(|l| split_list(&l)),
+            deny_licenses: var("MBREW_DENY_LICENSES").map(|l| split_list(&l)),
+        }
+    }
+
+    /// Make sure every value that's set is valid,
+    /// returns the name of the bad field along with the error
+    pub fn validate(&self) -> Result<(), (&'static str, ConfigError)> {
+        if let Some(target) = &self.target {
+            target.parse::<VersionRange>().map_err(|e| ("target", e))?;
+        }
+
+        if matches!(&self.side, Some(side) if !SIDES.contains(&side.as_str())) {
+            return Err(("side", ConfigError::InvalidChoice(&SIDES)));
+        }
+
+        if matches!(&self.channel, Some(ch) if !CHANNELS.contains(&ch.as_str())) {
+            return Err(("channel", ConfigError::InvalidChoice(&CHANNELS)));
+        }
+
+        if matches!(&self.api_url, Some(url) if !url.starts_with("http://") && !url.starts_with("https://")) {
+            return Err(("api_url", ConfigError::InvalidUrl));
+        }
+
+        if matches!(&self.proxy, Some(url) if !PROXY_SCHEMES.iter().any(|s| url.starts_with(s))) {
+            return Err(("proxy", ConfigError::InvalidProxy(&PROXY_SCHEMES)));
+        }
+
+        if matches!(&self.tls, Some(tls) if !TLS_BACKENDS.contains(&tls.as_str())) {
+            return Err(("tls", ConfigError::InvalidChoice(&TLS_BACKENDS)));
+        }
+
+        if self.concurrency == Some(0) {
+            return Err(("concurrency", ConfigError::NotPositive));
+        }
+
+        if self.timeout == Some(0) {
+            return Err(("timeout", ConfigError::NotPositive));
+        }
+
+        if let Some(Err(e)) = self.cache_ttl.as_deref().map(parse_duration) {
+            return Err(("cache_ttl", ConfigError::InvalidDuration(e)));
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&ConfigFile> for Layer {
+    fn from(cfg: &ConfigFile) -> Self {
+        Self {
+            mc_dir: cfg.mc_dir.clone(),
+            target: cfg.target.clone(),
+            loader: cfg.loader.clone(),
+            side: cfg.side.clone(),
+            channel: cfg.channel.clone(),
+            api_url: cfg.api_url.clone(),
+            concurrency: cfg.concurrency,
+            timeout: cfg.timeout,
+            cache_ttl: cfg.cache_ttl.clone(),
+            token: cfg.token.clone(),
+            proxy: cfg.proxy.clone(),
+            no_proxy: cfg.no_proxy.clone(),
+            ca_certs: cfg.ca_certs.clone(),
+            tls: cfg.tls.clone(),
+            allow_licenses: cfg.allow_licenses.clone(),
+            deny_licenses: cfg.deny_licenses.clone(),
+        }
+    }
+}
+
+impl From<Profile> for Layer {
+    fn from(p: Profile) -> Self {
+        Self {
+            mc_dir: Some(p.mc_dir),
+            target: Some(p.target),
+            loader: p.loader,
+            side: p.side,
+            channel: p.channel,
+            api_url: None,
+            concurrency: None,
+            timeout: None,
+            cache_ttl: None,
+            token: None,
+            proxy: None,
+            no_proxy: None,
+            ca_certs: None,
+            tls: None,
+            allow_licenses: None,
+            deny_licenses: None,
+        }
+    }
+}
+
+/// Every option after all the layers have been merged
+#[derive(Debug)]
+pub struct Settings {
+    pub mc_dir: PathBuf,
+    pub target: String,
+    pub loader: Option<String>,
+    pub side: Option<String>,
+    pub channel: Option<String>,
+    pub api_url: String,
+    pub concurrency: u64,
+    // seconds
+    pub timeout: u64,
+    // a length of time like "10m"
+    pub cache_ttl: String,
+    pub token: Option<String>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_certs: Vec<PathBuf>,
+    pub tls: String,
+    // SPDX ids, "arr" and "unknown"
+    pub allow_licenses: Option<Vec<String>>,
+    pub deny_licenses: Vec<String>,
+    pub profile: Option<String>,
+    pub instance: Option<String>,
+
+    // where each of the above came from, keyed by field name
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mc_dir: get_mc_dir(),
+            target: DEFAULT_MC_VERSION.to_string(),
+            loader: None,
+            side: None,
+            channel: None,
+            api_url: DEFAULT_API_URL.to_string(),
+            concurrency: DEFAULT_CONCURRENCY,
+            timeout: DEFAULT_TIMEOUT,
+            cache_ttl: DEFAULT_CACHE_TTL.to_string(),
+            token: None,
+            proxy: None,
+            no_proxy: None,
+            ca_certs: Vec::new(),
+            tls: TLS_BACKENDS[0].to_string(),
+            allow_licenses: None,
+            deny_licenses: Vec::new(),
+            profile: None,
+            instance: None,
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Merge every layer, `cli` holds the options passed on the command line
+    /// that are known before the arguments are parsed. Exits with a message
+    /// if any layer holds an invalid value
+    pub fn resolve(cli: Layer, profile: Option<String>, instance: Option<String>) -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let system = ConfigFile::read(&ConfigFile::system_path());
+        let user = ConfigFile::load();
+
+        let mut settings = Settings::default();
+        settings.apply(Layer { token: ConfigFile::read_token(), ..Layer::default() }, Source::TokenFile);
+
+        // the proxy variables every program uses sit below anything set for minebrew
+        let standard = |names: &[&'static str]| names.iter().find_map(|&n| var(n).map(|v| (n, v)));
+        if let Some((name, proxy)) = standard(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]) {
+            // they're often set without a scheme, ex: "proxy.example.com:3128"
+            let proxy = if proxy.contains("://") { proxy } else { format!("http://{proxy}") };
+            let layer = Layer { proxy: Some(proxy), ..Layer::default() };
+            if let Err((_, e)) = layer.validate() {
+                exit_with_msg(format!("Error with {name}: {e}"));
+            }
+            settings.apply(layer, Source::StandardEnv(name));
+        }
+        if let Some((name, no_proxy)) = standard(&["NO_PROXY", "no_proxy"]) {
+            settings.apply(Layer { no_proxy: Some(no_proxy), ..Layer::default() }, Source::StandardEnv(name));
+        }
+        settings.apply(Layer::from(&system), Source::SystemConfig);
+        settings.apply(Layer::from(&user), Source::UserConfig);
+
+        // the profile's options sit on top of the config file it's chosen in
+        let chosen = [
+            (profile, Source::Cli),
+            (var("MBREW_PROFILE"), Source::Env),
+            (user.profile.clone(), Source::UserConfig),
+            (system.profile.clone(), Source::SystemConfig),
+        ].into_iter().find_map(|(name, source)| name.map(|n| (n, source)));
+
+        if let Some((name, source)) = chosen {
+            let profile = user.profiles.get(&name)
+                .or_else(|| system.profiles.get(&name))
+                .cloned()
+                .unwrap_or_else(|| exit_with_msg(format!("Error with profile: {}", ConfigError::ProfileNotFound(name.clone()))));
+
+            settings.apply(profile.into(), Source::Profile(name.clone()));
+            settings.profile = Some(name);
+            settings.sources.insert("profile", source);
+        }
+
+        let env = Layer::from_env(var);
+        if let Err((field, e)) = env.validate() {
+            exit_with_msg(format!("Error with MBREW_{}: {e}", field.to_uppercase()));
+        }
+
+        // a launcher instance overrides whatever the config says about it,
+        // otherwise look inside the directory to see what it's set up for
+        let instance = [(instance, Source::Cli), (var("MBREW_INSTANCE"), Source::Env)]
+            .into_iter()
+            .find_map(|(name, source)| name.map(|n| (n, source)));
+
+        match instance {
+            Some((name, source)) => {
+                let found = instances::find(&name)
+                    .unwrap_or_else(|e| exit_with_msg(format!("Error with instance: {e}")));
+
+                settings.apply(Layer {
+                    mc_dir: Some(found.mc_dir),
+                    target: found.target,
+                    loader: found.loader,
+                    ..Layer::default()
+                }, Source::Instance(name.clone()));
+                settings.instance = Some(name);
+                settings.sources.insert("instance", source);
+            },
+            None => {
+                let mc_dir = cli.mc_dir.as_ref()
+                    .or(env.mc_dir.as_ref())
+                    .unwrap_or(&settings.mc_dir);
+                let inferred = infer::infer(mc_dir);
+
+                settings.apply(Layer {
+                    target: inferred.target,
+                    loader: inferred.loader,
+                    ..Layer::default()
+                }, Source::Inferred);
+            },
+        }
+
+        settings.apply(env, Source::Env);
+        settings.apply(cli, Source::Cli);
+        settings
+    }
+
+    /// Override every value `layer` sets
+    pub fn apply(&mut self, layer: Layer, source: Source) {
+        if let Some(mc_dir) = layer.mc_dir {
+            self.mc_dir = mc_dir;
+            self.sources.insert("mc_dir", source.clone());
+        }
+        if let Some(target) = layer.target {
+            self.target = target;
+            self.sources.insert("target", source.clone());
+        }
+        if let Some(loader) = layer.loader {
+            self.loader = Some(loader);
+            self.sources.insert("loader", source.clone());
+        }
+        if let Some(side) = layer.side {
+            self.side = Some(side);
+            self.sources.insert("side", source.clone());
+        }
+        if let Some(channel) = layer.channel {
+            self.channel = Some(channel);
+            self.sources.insert("channel", source.clone());
+        }
+        if let Some(api_url) = layer.api_url {
+            self.api_url = api_url;
+            self.sources.insert("api_url", source.clone());
+        }
+        if let Some(concurrency) = layer.concurrency {
+            self.concurrency = concurrency;
+            self.sources.insert("concurrency", source.clone());
+        }
+        if let Some(timeout) = layer.timeout {
+            self.timeout = timeout;
+            self.sources.insert("timeout", source.clone());
+        }
+        if let Some(cache_ttl) = layer.cache_ttl {
+            self.cache_ttl = cache_ttl;
+            self.sources.insert("cache_ttl", source.clone());
+        }
+        if let Some(token) = layer.token {
+            self.token = Some(token);
+            self.sources.insert("token", source.clone());
+        }
+        if let Some(proxy) = layer.proxy {
+            self.proxy = Some(proxy);
+            self.sources.insert("proxy", source.clone());
+        }
+        if let Some(no_proxy) = layer.no_proxy {
+            self.no_proxy = Some(no_proxy);
+            self.sources.insert("no_proxy", source.clone());
+        }
+        if let Some(ca_certs) = layer.ca_certs {
+            self.ca_certs = ca_certs;
+            self.sources.insert("ca_certs", source.clone());
+        }
+        if let Some(tls) = layer.tls {
+            self.tls = tls;
+            self.sources.insert("tls", source.clone());
+        }
+        if let Some(allow_licenses) = layer.allow_licenses {
+            self.allow_licenses = Some(allow_licenses);
+            self.sources.insert("allow_licenses", source.clone());
+        }
+        if let Some(deny_licenses) = layer.deny_licenses {
+            self.deny_licenses = deny_licenses;
+            self.sources.insert("deny_licenses", source);
+        }
+    }
+
+    /// Where the value of a field came from
+    pub fn source(&self, field: &str) -> &Source {
+        self.sources.get(field).unwrap_or(&Source::Default)
+    }
+
+    /// Every field with its value, if it's set, and a description of where it came from
+    pub fn explain(&self) -> Vec<(&'static str, Option<String>, String)> {
+        [
+            ("mc_dir", Some(self.mc_dir.display().to_string())),
+            ("target", Some(self.target.clone())),
+            ("loader", self.loader.clone()),
+            ("side", self.side.clone()),
+            ("channel", self.channel.clone()),
+            ("api_url", Some(self.api_url.clone())),
+            ("concurrency", Some(self.concurrency.to_string())),
+            ("timeout", Some(format!("{}s", self.timeout))),
+            ("cache_ttl", Some(self.cache_ttl.clone())),
+            // never print the token itself
+            ("token", self.token.as_ref().map(|_| "(hidden)".to_string())),
+            ("proxy", self.proxy.clone()),
+            ("no_proxy", self.no_proxy.clone()),
+            ("ca_certs", (!self.ca_certs.is_empty()).then(|| {
+                self.ca_certs.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+            })),
+            ("tls", Some(self.tls.clone())),
+            ("allow_licenses", self.allow_licenses.as_ref().map(|l| l.join(", "))),
+            ("deny_licenses", (!self.deny_licenses.is_empty()).then(|| self.deny_licenses.join(", "))),
+            ("profile", self.profile.clone()),
+            ("instance", self.instance.clone()),
+        ].into_iter()
+            .map(|(field, value)| (field, value, match self.source(field) {
+                Source::Env => format!("environment (MBREW_{})", field.to_uppercase()),
+                source => source.to_string(),
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let mut settings = Settings::default();
+        settings.apply(Layer {
+            target: Some("1.18.2".to_string()),
+            loader: Some("forge".to_string()),
+            ..Layer::default()
+        }, Source::UserConfig);
+        settings.apply(Layer {
+            target: Some("1.19.2".to_string()),
+            ..Layer::default()
+        }, Source::Cli);
+
+        assert_eq!(settings.target, "1.19.2");
+        assert_eq!(settings.source("target"), &Source::Cli);
+        assert_eq!(settings.loader.as_deref(), Some("forge"));
+        assert_eq!(settings.source("loader"), &Source::UserConfig);
+        assert_eq!(settings.source("mc_dir"), &Source::Default);
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let env = Layer::from_env(|name| match name {
+            "MBREW_TARGET" => Some("1.19.x".to_string()),
+            "MBREW_LOADER" => Some("Fabric".to_string()),
+            "MBREW_SIDE" => Some(String::new()),
+            _ => None,
+        });
+
+        assert_eq!(env.target.as_deref(), Some("1.19.x"));
+        assert_eq!(env.loader.as_deref(), Some("fabric"));
+        assert_eq!(env.side, None);
+        assert!(env.validate().is_ok());
+
+        let env = Layer::from_env(|name| (name == "MBREW_CHANNEL").then(|| "nightly".to_string()));
+        assert!(matches!(env.validate(), Err(("channel", _))));
+
+        let env = Layer::from_env(|name| (name == "MBREW_CONCURRENCY").then(|| "lots".to_string()));
+        assert!(matches!(env.validate(), Err(("concurrency", _))));
+
+        let env = Layer::from_env(|name| (name == "MBREW_CACHE_TTL").then(|| "10 minutes".to_string()));
+        assert!(matches!(env.validate(), Err(("cache_ttl", _))));
+
+        let env = Layer::from_env(|name| (name == "MBREW_TLS").then(|| "openssl".to_string()));
+        assert!(matches!(env.validate(), Err(("tls", _))));
+
+        let env = Layer::from_env(|name| (name == "MBREW_PROXY").then(|| "socks5h://127.0.0.1:1080".to_string()));
+        assert!(env.validate().is_ok());
+
+        let env = Layer::from_env(|name| (name == "MBREW_PROXY").then(|| "socks4://127.0.0.1:1080".to_string()));
+        assert!(matches!(env.validate(), Err(("proxy", _))));
+
+        let env = Layer::from_env(|name| (name == "MBREW_DENY_LICENSES").then(|| "arr, unknown,".to_string()));
+        assert_eq!(env.deny_licenses, Some(vec!["arr".to_string(), "unknown".to_string()]));
+    }
+}
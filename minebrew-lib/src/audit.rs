@@ -0,0 +1,137 @@
+use crate::modrinth::{Project, ProjectStatus, Version, VersionStatus};
+
+// An audit looks at what modrinth says about each installed mod now, rather
+// than when it was installed. Projects get archived or taken down, versions
+// get pulled and mods stop being updated for the version we play on, any of
+// which means it's time to look for a replacement
+
+/// Something wrong with an installed mod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// the project isn't on modrinth anymore
+    ProjectDeleted,
+
+    /// the project isn't approved anymore, ex: it was archived
+    ProjectStatus(ProjectStatus),
+
+    /// the installed version isn't on modrinth anymore
+    VersionDeleted,
+
+    /// the installed version was unlisted or archived
+    VersionStatus(VersionStatus),
+
+    /// the newest version of the mod doesn't support the target
+    LatestUnsupported { version_number: String, game_versions: Vec<String> },
+}
+
+impl Issue {
+    /// A short name for the kind of issue, for scripts
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::ProjectDeleted => "project_deleted",
+            Issue::ProjectStatus(_) => "project_status",
+            Issue::VersionDeleted => "version_deleted",
+            Issue::VersionStatus(_) => "version_status",
+            Issue::LatestUnsupported { .. } => "latest_unsupported",
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::ProjectDeleted => write!(f, "the project has been deleted from modrinth"),
+            Issue::ProjectStatus(status) => write!(f, "the project is {status}"),
+            Issue::VersionDeleted => write!(f, "the installed version has been deleted"),
+            Issue::VersionStatus(status) => write!(f, "the installed version was yanked, it's {status} now"),
+            Issue::LatestUnsupported { version_number, game_versions } => write!(f,
+                "the newest version, {version_number}, doesn't support the target (supports {})",
+                game_versions.join(", ")),
+        }
+    }
+}
+
+/// Statuses a project that's still looked after doesn't have
+fn abandoned(status: ProjectStatus) -> bool {
+    matches!(status,
+        ProjectStatus::Archived | ProjectStatus::Unlisted | ProjectStatus::Rejected
+        | ProjectStatus::Withheld | ProjectStatus::Draft | ProjectStatus::Private)
+}
+
+/// Statuses of a version that has been pulled
+fn yanked(status: VersionStatus) -> bool {
+    matches!(status, VersionStatus::Archived | VersionStatus::Unlisted | VersionStatus::Draft)
+}
+
+/// Find everything wrong with an installed mod from its `project`, the
+/// installed `version` and the `latest` version for its loader, each is
+/// `None` when modrinth doesn't have it anymore
+pub fn audit(project: Option<&Project>, version: Option<&Version>, latest: Option<&Version>, targets: &[String]) -> Vec<Issue> {
+    let project = match project {
+        Some(project) => project,
+        None => return vec![Issue::ProjectDeleted],
+    };
+
+    let mut issues = Vec::new();
+    if abandoned(project.status) {
+        issues.push(Issue::ProjectStatus(project.status));
+    }
+
+    match version.map(|v| v.status) {
+        None => issues.push(Issue::VersionDeleted),
+        Some(Some(status)) if yanked(status) => issues.push(Issue::VersionStatus(status)),
+        Some(_) => {},
+    }
+
+    if let Some(latest) = latest {
+        if !latest.game_versions.iter().any(|v| targets.contains(v)) {
+            issues.push(Issue::LatestUnsupported {
+                version_number: latest.version_number.clone(),
+                game_versions: latest.game_versions.clone(),
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use super::*;
+
+    fn project(status: &str) -> Project {
+        serde_json::from_value(serde_json::json!({
+            "id": "AABBCCDD", "slug": "sodium", "title": "Sodium", "description": "",
+            "project_type": "mod", "client_side": "required", "server_side": "unsupported",
+            "status": status, "game_versions": [], "loaders": ["fabric"],
+        })).unwrap()
+    }
+
+    fn version(number: &str, status: &str, game_versions: &[&str]) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "id": number, "project_id": "AABBCCDD", "name": number, "version_number": number, "status": status,
+            "game_versions": game_versions, "version_type": "release", "loaders": ["fabric"], "files": [],
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_audit() {
+        let targets = ["1.20.1".to_string()];
+        let approved = project("approved");
+        let installed = version("0.5.1", "listed", &["1.20.1"]);
+
+        assert!(audit(Some(&approved), Some(&installed), Some(&installed), &targets).is_empty());
+        assert_eq!(audit(None, None, None, &targets), [Issue::ProjectDeleted]);
+
+        let archived = project("archived");
+        let pulled = version("0.5.1", "archived", &["1.20.1"]);
+        let moved_on = version("0.6.0", "listed", &["1.21"]);
+        assert_eq!(audit(Some(&archived), Some(&pulled), Some(&moved_on), &targets), [
+            Issue::ProjectStatus(ProjectStatus::Archived),
+            Issue::VersionStatus(VersionStatus::Archived),
+            Issue::LatestUnsupported { version_number: "0.6.0".to_string(), game_versions: vec!["1.21".to_string()] },
+        ]);
+
+        assert_eq!(audit(Some(&approved), None, Some(&installed), &targets), [Issue::VersionDeleted]);
+    }
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// A content addressed store of every file Minebrew has downloaded, shared
+// by all minecraft directories. Files are kept by their sha512 hash at
+// "files/<first 2 characters>/<hash>/<filename>" so the same jar is only
+// downloaded once no matter how many instances use it. Files are hardlinked
+// into place when the filesystem allows it and copied when it doesn't
+//
+// A file's modification time is bumped every time it's used so old entries
+// can be pruned by when they were last used
+//
+// The body of every api response is also kept, at "responses/<url hash>.json",
+// so searches and version lookups can be answered again without a connection.
+// Along with the body the response's ETag and Last-Modified headers are kept
+// so a stale response can be revalidated instead of fetched again
+
+/// What a cache entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// a downloaded file
+    File,
+    /// a response from the api
+    Response,
+}
+
+/// A file in the cache
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub kind: EntryKind,
+    // the hash of the file, or of the url for a response
+    pub sha512: String,
+    pub filename: String,
+    pub size: u64,
+    pub last_used: SystemTime,
+    pub path: PathBuf,
+}
+
+/// A response from the api as it was when it was last fetched
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub body: String,
+    // seconds since the unix epoch
+    pub fetched: u64,
+    // validators sent back with conditional requests to see if the body changed
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+impl CachedResponse {
+    /// A response that was just fetched
+    pub fn new(url: &str, body: String, etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self { url: url.to_string(), body, fetched: now_secs(), etag, last_modified }
+    }
+
+    /// How long ago the response was fetched or last revalidated
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now_secs().saturating_sub(self.fetched))
+    }
+
+    /// The server said the body hasn't changed, so it's as good as just fetched
+    pub fn revalidated(&mut self) {
+        self.fetched = now_secs();
+    }
+}
+
+/// Minebrew's download cache
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Default for Cache {
+    /// The cache in the user's cache directory,
+    /// ex: "~/.cache/minebrew"
+    fn default() -> Self {
+        let dir = dirs::cache_dir().unwrap_or_else(std::env::temp_dir);
+        Self::at(dir.join("minebrew"))
+    }
+}
+
+impl Cache {
+    /// Use the cache stored in `root`
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Folder the files of every hash are kept in
+    fn files_dir(&self) -> PathBuf {
+        self.root.join("files")
+    }
+
+    /// Folder a file with this hash is kept in
+    fn hash_dir(&self, sha512: &str) -> PathBuf {
+        self.files_dir().join(sha512.get(..2).unwrap_or("__")).join(sha512)
+    }
+
+    /// Folder every response is kept in
+    fn responses_dir(&self) -> PathBuf {
+        self.root.join("responses")
+    }
+
+    /// Where the response for `url` is kept
+    fn response_path(&self, url: &str) -> PathBuf {
+        let hash = format!("{:x}", Sha512::digest(url.as_bytes()));
+        self.responses_dir().join(format!("{}.json", &hash[..32]))
+    }
+
+    /// The last response fetched from `url`, if there's been one
+    pub fn response(&self, url: &str) -> Option<CachedResponse> {
+        let text = std::fs::read_to_string(self.response_path(url)).ok()?;
+        serde_json::from_str::<CachedResponse>(&text).ok()
+            .filter(|r| r.url == url)
+    }
+
+    /// Keep a response so it can be reused until it goes stale and offline
+    pub fn store_response(&self, resp: &CachedResponse) -> Result<()> {
+        let path = self.response_path(&resp.url);
+        std::fs::create_dir_all(path.parent().unwrap())
+            .with_context(|| format!("Error creating \"{}\"", path.parent().unwrap().display()))?;
+
+        std::fs::write(&path, serde_json::to_string(resp)?)
+            .with_context(|| format!("Error writing \"{}\"", path.display()))
+    }
+
+    /// Where a file is written while it's downloading, it's moved into
+    /// the cache once it's been verified. Staged files are named by their
+    /// hash so different files that share a filename don't collide, the
+    /// filename is only used when the hash isn't known
+    pub fn staging_path(&self, sha512: &str, filename: &str) -> Result<PathBuf> {
+        let dir = self.root.join("staging");
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Error creating \"{}\"", dir.display()))?;
+        if sha512.is_empty() {
+            return Ok(dir.join(filename));
+        }
+        Ok(dir.join(sha512))
+    }
+
+    /// Returns the cached file with this hash if there is one,
+    /// marking it as just used
+    pub fn get(&self, sha512: &str) -> Option<PathBuf> {
+        if sha512.is_empty() {
+            return None;
+        }
+
+        let path = std::fs::read_dir(self.hash_dir(sha512)).ok()?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .find(|p| p.is_file())?;
+
+        touch(&path);
+        Some(path)
+    }
+
+    /// Move a downloaded file into the cache under its hash,
+    /// returns where it ended up
+    pub fn insert(&self, sha512: &str, filename: &str, from: &Path) -> Result<PathBuf> {
+        let dir = self.hash_dir(sha512);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Error creating \"{}\"", dir.display()))?;
+
+        let path = dir.join(filename);
+        std::fs::rename(from, &path)
+            .with_context(|| format!("Error moving \"{}\" into the cache", from.display()))?;
+        Ok(path)
+    }
+
+    /// Put a cached file at `dest`, hardlinking it if possible
+    /// and copying it if not, anything already at `dest` is replaced
+    pub fn install(&self, cached: &Path, dest: &Path) -> Result<()> {
+        if dest.exists() {
+            std::fs::remove_file(dest)
+                .with_context(|| format!("Error replacing \"{}\"", dest.display()))?;
+        }
+
+        if std::fs::hard_link(cached, dest).is_err() {
+            std::fs::copy(cached, dest)
+                .with_context(|| format!("Error copying \"{}\"", dest.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Every file and response in the cache, a response is 
+    /// last used when it was last fetched or revalidated
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+
+        // responses/<url hash>.json
+        if let Some(responses) = read_dir_if_exists(&self.responses_dir())? {
+            for file in responses {
+                let file = file?;
+                let meta = file.metadata()?;
+                let filename = file.file_name().to_string_lossy().to_string();
+                entries.push(CacheEntry {
+                    kind: EntryKind::Response,
+                    sha512: filename.trim_end_matches(".json").to_string(),
+                    filename,
+                    size: meta.len(),
+                    last_used: meta.modified()?,
+                    path: file.path(),
+                });
+            }
+        }
+
+        let prefixes = match read_dir_if_exists(&self.files_dir())? {
+            Some(prefixes) => prefixes,
+            None => return Ok(entries),
+        };
+
+        // files/<prefix>/<hash>/<filename>
+        for hash_dir in prefixes.filter_map(Result::ok).flat_map(|p| std::fs::read_dir(p.path()).into_iter().flatten()) {
+            let hash_dir = hash_dir?;
+            for file in std::fs::read_dir(hash_dir.path())? {
+                let file = file?;
+                let meta = file.metadata()?;
+                entries.push(CacheEntry {
+                    kind: EntryKind::File,
+                    sha512: hash_dir.file_name().to_string_lossy().to_string(),
+                    filename: file.file_name().to_string_lossy().to_string(),
+                    size: meta.len(),
+                    last_used: meta.modified()?,
+                    path: file.path(),
+                });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Total size in bytes of every file and response in the cache
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|e| e.size).sum())
+    }
+
+    /// Remove every file and response in the cache, returns 
+    /// how many were removed and how many bytes they took up
+    pub fn clean(&self) -> Result<(usize, u64)> {
+        self.prune(Duration::ZERO)
+    }
+
+    /// Remove the files and responses that haven't been used in `older_than`,
+    /// returns how many were removed and how many bytes they took up
+    pub fn prune(&self, older_than: Duration) -> Result<(usize, u64)> {
+        let now = SystemTime::now();
+        let mut removed = (0, 0);
+
+        for entry in self.entries()? {
+            let age = now.duration_since(entry.last_used).unwrap_or_default();
+            if age >= older_than {
+                std::fs::remove_file(&entry.path)
+                    .with_context(|| format!("Error removing \"{}\"", entry.path.display()))?;
+
+                // the same file can be kept under more than one name
+                let hash_dir = entry.path.parent().unwrap();
+                if entry.kind == EntryKind::File && std::fs::read_dir(hash_dir).is_ok_and(|mut files| files.next().is_none()) {
+                    std::fs::remove_dir(hash_dir)
+                        .with_context(|| format!("Error removing \"{}\"", hash_dir.display()))?;
+                }
+                removed.0 += 1;
+                removed.1 += entry.size;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// The contents of a folder, `None` if it hasn't been created yet
+fn read_dir_if_exists(dir: &Path) -> Result<Option<std::fs::ReadDir>> {
+    match std::fs::read_dir(dir) {
+        Ok(files) => Ok(Some(files)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Error reading the cache"),
+    }
+}
+
+/// Seconds since the unix epoch
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Mark a file as just used
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        file.set_modified(SystemTime::now()).ok();
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    fn temp_cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("minebrew-cache-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        Cache::at(dir)
+    }
+
+    #[test]
+    fn test_insert_and_install() {
+        let cache = temp_cache("install");
+        assert!(cache.get("abcdef").is_none());
+
+        let staged = cache.staging_path("abcdef", "sodium.jar").unwrap();
+        assert_ne!(staged, cache.staging_path("123456", "sodium.jar").unwrap());
+        assert!(cache.staging_path("", "sodium.jar").unwrap().ends_with("sodium.jar"));
+        std::fs::write(&staged, b"jar").unwrap();
+        let cached = cache.insert("abcdef", "sodium.jar", &staged).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(cache.get("abcdef"), Some(cached.clone()));
+
+        // installing over an old file replaces it
+        let dest = cache.root().join("sodium-installed.jar");
+        std::fs::write(&dest, b"old").unwrap();
+        cache.install(&cached, &dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"jar");
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].filename, "sodium.jar");
+        assert_eq!(cache.size().unwrap(), 3);
+
+        std::fs::remove_dir_all(cache.root()).ok();
+    }
+
+    #[test]
+    fn test_prune() {
+        let cache = temp_cache("prune");
+        for (hash, name) in [("aa11", "a.jar"), ("bb22", "b.jar"), ("bb22", "b-renamed.jar")] {
+            let staged = cache.staging_path(hash, name).unwrap();
+            std::fs::write(&staged, b"12345").unwrap();
+            cache.insert(hash, name, &staged).unwrap();
+        }
+
+        // nothing has gone unused for a day yet
+        assert_eq!(cache.prune(Duration::from_secs(60 * 60 * 24)).unwrap(), (0, 0));
+
+        // a file kept under two names keeps its folder until both are gone
+        let renamed = cache.hash_dir("bb22").join("b-renamed.jar");
+        let two_days_ago = SystemTime::now() - Duration::from_secs(60 * 60 * 48);
+        std::fs::File::options().append(true).open(&renamed).unwrap().set_modified(two_days_ago).unwrap();
+        assert_eq!(cache.prune(Duration::from_secs(60 * 60 * 24)).unwrap(), (1, 5));
+        assert!(!renamed.exists());
+        assert!(cache.get("bb22").is_some());
+
+        assert_eq!(cache.clean().unwrap(), (2, 10));
+        assert!(cache.entries().unwrap().is_empty());
+        assert!(!cache.hash_dir("bb22").exists());
+
+        std::fs::remove_dir_all(cache.root()).ok();
+    }
+
+    #[test]
+    fn test_responses() {
+        let cache = temp_cache("responses");
+        let url = "https://api.modrinth.com/v2/project/sodium";
+        assert!(cache.response(url).is_none());
+
+        let mut resp = CachedResponse::new(url, "{\"slug\":\"sodium\"}".to_string(), Some("\"abc\"".to_string()), None);
+        resp.fetched -= 60 * 60;
+        cache.store_response(&resp).unwrap();
+
+        let mut cached = cache.response(url).unwrap();
+        assert_eq!(cached.body, "{\"slug\":\"sodium\"}");
+        assert_eq!(cached.etag.as_deref(), Some("\"abc\""));
+        assert!(cached.age() >= Duration::from_secs(60 * 60));
+        cached.revalidated();
+        assert!(cached.age() < Duration::from_secs(60));
+        assert!(cache.response("https://api.modrinth.com/v2/project/lithium").is_none());
+
+        // responses take up space and are cleaned like files are
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, EntryKind::Response);
+        assert!(cache.size().unwrap() > 0);
+        assert_eq!(cache.clean().unwrap().0, 1);
+        assert!(cache.response(url).is_none());
+
+        std::fs::remove_dir_all(cache.root()).ok();
+    }
+}
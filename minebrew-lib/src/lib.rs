@@ -1,2 +1,7 @@
 // Modules Used -- These are all the files within the lib directory
 pub mod modrinth; // modrinth module has all the modrinth specific code
+pub mod state;    // state module keeps track of installed and pinned mods
+pub mod cache;    // cache module stores downloaded files by their hash
+pub mod progress; // progress module lets library users follow installs as they happen
+pub mod audit;    // audit module finds installed mods that need replacing
+pub mod license;  // license module checks installed mods against a license policy
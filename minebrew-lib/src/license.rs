@@ -0,0 +1,139 @@
+use crate::modrinth::License;
+
+// Modrinth gives every project's license as an SPDX id, licenses that aren't
+// on the SPDX list use a "LicenseRef-" id, the two that matter most being
+// all rights reserved and unknown. Neither says what we're allowed to do with
+// the mod so both are flagged, and a policy can refuse them, or any other
+// license, from ever being installed
+
+/// The id modrinth uses for projects that are "All Rights Reserved"
+pub const ALL_RIGHTS_RESERVED: &str = "LicenseRef-All-Rights-Reserved";
+
+/// The id modrinth uses for projects that haven't said what their license is
+pub const UNKNOWN: &str = "LicenseRef-Unknown";
+
+/// The SPDX id of a project's license, one without a license is unknown
+pub fn spdx_id(license: Option<&License>) -> &str {
+    match license {
+        Some(l) if !l.id.is_empty() => &l.id,
+        _ => UNKNOWN,
+    }
+}
+
+/// A link to the text of a license, spdx.org is used for
+/// standard licenses modrinth doesn't have a link for
+pub fn url(license: Option<&License>) -> Option<String> {
+    let license = license?;
+    match &license.url {
+        Some(url) => Some(url.clone()),
+        None if !license.id.is_empty() && !license.id.starts_with("LicenseRef-") =>
+            Some(format!("https://spdx.org/licenses/{}.html", license.id)),
+        None => None,
+    }
+}
+
+/// Why a license needs a closer look
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concern {
+    /// nothing can be done with the mod without asking its author
+    AllRightsReserved,
+
+    /// the project hasn't said what its license is
+    Unknown,
+
+    /// the license policy refuses it
+    NotAllowed,
+}
+
+impl std::fmt::Display for Concern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Concern::AllRightsReserved => write!(f, "all rights reserved"),
+            Concern::Unknown => write!(f, "unknown license"),
+            Concern::NotAllowed => write!(f, "not allowed by the license policy"),
+        }
+    }
+}
+
+/// Why a license needs a closer look, `None` when it doesn't
+pub fn concern(id: &str) -> Option<Concern> {
+    if id.eq_ignore_ascii_case(ALL_RIGHTS_RESERVED) || id.eq_ignore_ascii_case("arr") {
+        Some(Concern::AllRightsReserved)
+    } else if id.is_empty() || id.eq_ignore_ascii_case(UNKNOWN) {
+        Some(Concern::Unknown)
+    } else {
+        None
+    }
+}
+
+/// Which licenses are allowed to be installed. Entries are SPDX ids,
+/// "arr" and "unknown" match those kinds of licenses whatever their id
+#[derive(Debug, Default, Clone)]
+pub struct LicensePolicy {
+    /// when set, only these licenses are allowed
+    pub allow: Option<Vec<String>>,
+
+    /// these licenses are never allowed
+    pub deny: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// True when there's nothing to enforce
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_none() && self.deny.is_empty()
+    }
+
+    /// Returns true if a project with the license `id` can be installed
+    pub fn allows(&self, id: &str) -> bool {
+        let listed = |entries: &[String]| entries.iter().any(|e| matches(e, id));
+        !listed(&self.deny) && self.allow.as_deref().is_none_or(listed)
+    }
+}
+
+/// Does a policy entry cover the license `id`
+fn matches(entry: &str, id: &str) -> bool {
+    entry.eq_ignore_ascii_case(id) || match concern(id) {
+        Some(Concern::AllRightsReserved) => entry.eq_ignore_ascii_case("arr"),
+        Some(Concern::Unknown) => entry.eq_ignore_ascii_case("unknown"),
+        Some(Concern::NotAllowed) | None => false,
+    }
+}
+
+#[cfg(test)]
+mod license_tests {
+    use super::*;
+
+    fn license(id: &str, url: Option<&str>) -> License {
+        License { id: id.to_string(), name: String::new(), url: url.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_license_info() {
+        let lgpl = license("LGPL-3.0-only", None);
+        assert_eq!(spdx_id(Some(&lgpl)), "LGPL-3.0-only");
+        assert_eq!(url(Some(&lgpl)).as_deref(), Some("https://spdx.org/licenses/LGPL-3.0-only.html"));
+        assert_eq!(concern("LGPL-3.0-only"), None);
+
+        let custom = license("LicenseRef-Custom", Some("https://example.com/LICENSE"));
+        assert_eq!(url(Some(&custom)).as_deref(), Some("https://example.com/LICENSE"));
+        assert_eq!(url(Some(&license(ALL_RIGHTS_RESERVED, None))), None);
+
+        assert_eq!(spdx_id(None), UNKNOWN);
+        assert_eq!(concern(UNKNOWN), Some(Concern::Unknown));
+        assert_eq!(concern(ALL_RIGHTS_RESERVED), Some(Concern::AllRightsReserved));
+    }
+
+    #[test]
+    fn test_policy() {
+        assert!(LicensePolicy::default().allows(ALL_RIGHTS_RESERVED));
+
+        let deny = LicensePolicy { allow: None, deny: vec!["arr".to_string(), "unknown".to_string()] };
+        assert!(!deny.allows(ALL_RIGHTS_RESERVED));
+        assert!(!deny.allows(UNKNOWN));
+        assert!(deny.allows("MIT"));
+
+        let allow = LicensePolicy { allow: Some(vec!["mit".to_string(), "Apache-2.0".to_string()]), deny: Vec::new() };
+        assert!(allow.allows("MIT"));
+        assert!(!allow.allows("GPL-3.0-only"));
+    }
+}
@@ -0,0 +1,234 @@
+use super::search::SearchResult;
+
+// This module decides how well a search result matches what the user
+// typed. Titles and slugs are normalized first so that "Fabric API",
+// "fabric-api" and "fabric_api" are all treated the same, then scored
+// on exact, prefix and token matches falling back to edit distance.
+// Popularity (downloads and follows) only ever nudges the order of
+// results that already match, it can't make a bad match look good
+
+/// Results whose title and slug both score below this are dropped
+const MIN_SCORE: f64 = 0.5;
+
+/// How much popularity can add to a result's score at most
+const POPULARITY_WEIGHT: f64 = 0.1;
+
+/// How well a search result matches a query
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Relevance {
+    /// the query is the project's slug, nothing else needs to be considered
+    ExactSlug,
+
+    /// a score of how closely the query matches, higher is better
+    Score(f64),
+}
+
+/// Lowercases a string and replaces any run of punctuation or
+/// whitespace with a single space
+pub fn normalize(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The levenshtein distance between two strings counted in chars
+/// (not bytes) so non-ASCII titles are compared correctly
+pub fn levenshtein(s1: &str, s2: &str) -> usize {
+    let s1: Vec<char> = s1.chars().collect();
+    let s2: Vec<char> = s2.chars().collect();
+
+    let (short, long) = if s1.len() > s2.len() {
+        (s2, s1)
+    } else {
+        (s1, s2)
+    };
+
+    let n = long.len();
+
+    let mut costs: Vec<usize> = (0..n+1).collect();
+    for (i, c) in short.iter().enumerate() {
+        costs[0] = i + 1;
+        let mut corner = i;
+        for (j, c2) in long.iter().enumerate() {
+            let upper = costs[j + 1];
+            if c == c2 {
+                costs[j + 1] = corner;
+            } else {
+                costs[j + 1] = 1 + [costs[j], upper, corner].iter().min().unwrap();
+            }
+            corner = upper
+        }
+    }
+    costs[n]
+}
+
+/// Scores how well an already normalized query matches an already
+/// normalized title or slug, from 0.0 (nothing alike) to 1.0 (equal)
+fn text_score(query: &str, text: &str) -> f64 {
+    if query.is_empty() || text.is_empty() {
+        return 0.0;
+    }
+
+    if query == text || query.replace(' ', "") == text.replace(' ', "") {
+        return 1.0;
+    }
+
+    // "iris" -> "iris shaders"
+    let prefix: f64 = if text.starts_with(query) { 0.9 } else { 0.0 };
+
+    // fraction of the query's words that start one of the text's words
+    let words: Vec<&str> = text.split(' ').collect();
+    let query_words: Vec<&str> = query.split(' ').collect();
+    let found = query_words.iter()
+        .filter(|qw| words.iter().any(|w| w.starts_with(*qw)))
+        .count();
+    let tokens = 0.8 * found as f64 / query_words.len() as f64;
+
+    // plain edit distance as a fallback for typos
+    let longest = query.chars().count().max(text.chars().count());
+    let similarity = 1.0 - levenshtein(query, text) as f64 / longest as f64;
+
+    prefix.max(tokens).max(similarity)
+}
+
+/// A score from 0.0 to 1.0 of how popular a project is, based on
+/// the orders of magnitude of its downloads and follows
+fn popularity(downloads: usize, follows: usize) -> f64 {
+    let downloads = (downloads as f64 + 1.0).log10() / 8.0; // 100M downloads
+    let follows = (follows as f64 + 1.0).log10() / 6.0;     // 1M follows
+    ((downloads + follows) / 2.0).min(1.0)
+}
+
+/// Decide how relevant a search result is to a query, returns `None`
+/// if it isn't similar enough to be worth showing
+pub fn relevance(query: &str, result: &SearchResult) -> Option<Relevance> {
+    if query.trim().eq_ignore_ascii_case(&result.slug) {
+        return Some(Relevance::ExactSlug);
+    }
+
+    let query = normalize(query);
+    let score = text_score(&query, &normalize(&result.title))
+        .max(text_score(&query, &normalize(&result.slug)));
+
+    if score < MIN_SCORE {
+        return None;
+    }
+
+    Some(Relevance::Score(score + POPULARITY_WEIGHT * popularity(result.downloads, result.follows)))
+}
+
+/// Drops search results that don't match the query and sorts the rest
+/// best match first. An exact slug match is the only result kept
+pub fn rank(query: &str, hits: &mut Vec<SearchResult>) {
+    if let Some(i) = hits.iter().position(|r| relevance(query, r) == Some(Relevance::ExactSlug)) {
+        let exact = hits.swap_remove(i);
+        hits.clear();
+        hits.push(exact);
+        return;
+    }
+
+    let mut scored: Vec<(f64, SearchResult)> = hits.drain(..)
+        .filter_map(|r| match relevance(query, &r) {
+            Some(Relevance::Score(s)) => Some((s, r)),
+            _ => None,
+        })
+        .collect();
+
+    // sort descending, scores are never NaN
+    scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+    hits.extend(scored.into_iter().map(|(_, r)| r));
+}
+
+#[cfg(test)]
+mod matching_tests {
+    use super::*;
+
+    fn result(slug: &str, title: &str, downloads: usize) -> SearchResult {
+        serde_json::from_value(serde_json::json!({
+            "slug": slug,
+            "title": title,
+            "description": "",
+            "client_side": "required",
+            "server_side": "optional",
+            "project_type": "mod",
+            "downloads": downloads,
+            "follows": 0,
+            "versions": ["1.19.2"],
+        })).unwrap()
+    }
+
+    fn slugs(hits: &[SearchResult]) -> Vec<&str> {
+        hits.iter().map(|r| r.slug.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Fabric API"), "fabric api");
+        assert_eq!(normalize("fabric-api"), "fabric api");
+        assert_eq!(normalize("  Xaero's Minimap "), "xaero s minimap");
+        assert_eq!(normalize("Ünlimited__Öres"), "ünlimited öres");
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("sodium", "sodium"), 0);
+        assert_eq!(levenshtein("sodium", "sodum"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("sitting", "kitten"), 3);
+        // multi-byte chars count once
+        assert_eq!(levenshtein("über", "uber"), 1);
+        assert_eq!(levenshtein("日本語", "日本"), 1);
+    }
+
+    #[test]
+    fn test_relevance() {
+        let fabric_api = result("fabric-api", "Fabric API", 30_000_000);
+        assert_eq!(relevance("fabric-api", &fabric_api), Some(Relevance::ExactSlug));
+        assert_eq!(relevance("Fabric-API", &fabric_api), Some(Relevance::ExactSlug));
+        assert!(matches!(relevance("Fabric API", &fabric_api), Some(Relevance::Score(s)) if s >= 1.0));
+        assert!(matches!(relevance("fabricapi", &fabric_api), Some(Relevance::Score(s)) if s >= 1.0));
+
+        let iris = result("iris", "Iris Shaders", 10_000_000);
+        assert!(relevance("iris shaders", &iris).is_some());
+        assert!(relevance("shaders", &iris).is_some());
+
+        let minimap = result("xaeros-minimap", "Xaero's Minimap", 5_000_000);
+        assert!(relevance("xaeros minimap", &minimap).is_some());
+        assert!(relevance("sodium", &minimap).is_none());
+
+        // non-ASCII titles shouldn't panic
+        let umlaut = result("ueber-mod", "Über Möd", 10);
+        assert!(relevance("uber mod", &umlaut).is_some());
+    }
+
+    #[test]
+    fn test_rank() {
+        let mut hits = vec![
+            result("indium", "Indium", 5_000_000),
+            result("sodium-extra", "Sodium Extra", 4_000_000),
+            result("sodium", "Sodium", 20_000_000),
+        ];
+        rank("sodium", &mut hits);
+        assert_eq!(slugs(&hits), ["sodium"]);
+
+        let mut hits = vec![
+            result("reeses-sodium-options", "Reese's Sodium Options", 3_000_000),
+            result("lithium", "Lithium", 15_000_000),
+            result("sodium-extra", "Sodium Extra", 4_000_000),
+        ];
+        rank("Sodium Extra", &mut hits);
+        assert_eq!(slugs(&hits)[0], "sodium-extra");
+        assert!(!slugs(&hits).contains(&"lithium"));
+
+        // popularity breaks ties between equally good matches
+        let mut hits = vec![
+            result("modmenu-fork", "Mod Menu", 1_000),
+            result("modmenu-2", "Mod Menu", 10_000_000),
+        ];
+        rank("Mod Menu", &mut hits);
+        assert_eq!(slugs(&hits), ["modmenu-2", "modmenu-fork"]);
+    }
+}
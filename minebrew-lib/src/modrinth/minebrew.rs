@@ -1,89 +1,1367 @@
-use reqwest::Client;
-use anyhow::Result;
+use reqwest::{Certificate, Client, Method, NoProxy, Proxy, Response, StatusCode};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::json;
 use futures::stream::{self, StreamExt};
 
-use super::search::{Search, SearchResponse, SearchResult};
-use super::version::{Version, ModFile};
+use super::search::{Search, SearchResponse};
+use super::project::Project;
+use super::shared::{DependencyType, GameVersionTag, HashAlgorithm};
+use super::version::{Version, VersionType, ModFile, Candidate};
+use super::retry::{rate_limit_reset, RetryPolicy};
+use crate::state::State;
+use crate::cache::{Cache, CachedResponse};
+use crate::progress::{NoProgress, Progress};
 
+use sha2::{Digest, Sha512};
+
+use std::collections::HashMap;
+use std::future::Future;
 use std::io::Write;
-use std::path::Path;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A response from the api, read in full
+struct ApiResponse {
+    status: StatusCode,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+/// Base url of modrinth's api
+pub const MODRINTH_API: &str = "https://api.modrinth.com/v2";
+
+/// How long a cached response is used before it's revalidated
+pub const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Most requests made at once unless told otherwise
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// How long to wait for a response unless told otherwise
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// User-Agent sent with every request unless told otherwise, 
+/// modrinth asks for one that says what's making the request
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    "nmandrus1/Minebrew/", env!("CARGO_PKG_VERSION"), " (github.com/nmandrus1/Minebrew)");
 
 /// Minebrew struct is going to do all the interfacing between 
 /// modrinth.com and the user as well as contain all the info 
 /// on currently installed packages
 pub struct Minebrew {
-    client: Client
-    //db goes here
+    client: Client,
+
+    // base url every request is made against, without a trailing slash
+    api_url: String,
+
+    // modrinth personal access token, only ever sent to `api_url`
+    token: Option<HeaderValue>,
+
+    // downloaded files shared between every minecraft directory
+    cache: Cache,
+
+    // answer everything from the cache instead of the network
+    offline: bool,
+
+    // ignore cached responses and fetch everything again
+    refresh: bool,
+
+    // how long a cached response is trusted without asking the api
+    ttl: Duration,
+
+    // most requests in flight at once
+    concurrency: usize,
+
+    // how long to wait for a response before giving up on a try
+    timeout: Duration,
+
+    retry: RetryPolicy,
+
+    // set when the rate limit runs out, nothing is sent before then
+    rate_limited_until: Mutex<Option<Instant>>,
+
+    // told about everything that happens while installing
+    progress: Box<dyn Progress>,
 }
 
 /// Default init for Minebrew
 impl Default for Minebrew {
     fn default() -> Self {
-        Self { client: Client::new() }
+        Self::new(MODRINTH_API)
+    }
+}
+
+/// Which TLS implementation connections are made with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsBackend {
+    /// the operating system's, which trusts the same certificates as everything else on it
+    #[default]
+    Native,
+
+    /// rustls, with the Mozilla root certificates built in
+    Rustls,
+}
+
+impl FromStr for TlsBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "native" => Ok(TlsBackend::Native),
+            "rustls" => Ok(TlsBackend::Rustls),
+            _ => bail!("\"{s}\" isn't a tls backend, use native or rustls"),
+        }
+    }
+}
+
+/// Sets up how a `Minebrew` talks to the api, 
+/// every request it makes goes through the client built here
+pub struct MinebrewBuilder {
+    api_url: String,
+    user_agent: String,
+    token: Option<String>,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    root_certificates: Vec<PathBuf>,
+    tls: TlsBackend,
+    cache: Cache,
+    offline: bool,
+    refresh: bool,
+    ttl: Duration,
+    concurrency: usize,
+    retry: RetryPolicy,
+    progress: Box<dyn Progress>,
+}
+
+impl Default for MinebrewBuilder {
+    fn default() -> Self {
+        Self {
+            api_url: MODRINTH_API.to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            token: None,
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: None,
+            proxy: None,
+            no_proxy: None,
+            root_certificates: Vec::new(),
+            tls: TlsBackend::default(),
+            cache: Cache::default(),
+            offline: false,
+            refresh: false,
+            ttl: DEFAULT_TTL,
+            concurrency: DEFAULT_CONCURRENCY,
+            retry: RetryPolicy::default(),
+            progress: Box::new(NoProgress),
+        }
+    }
+}
+
+impl MinebrewBuilder {
+    /// Make requests against a modrinth compatible api other than modrinth.com
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// What the requests say is making them
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Modrinth personal access token, needed to see private projects. 
+    /// It's only sent to the api, never with downloads
+    pub fn token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// How long to wait for a response before trying again
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How long to wait for a connection to be made
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Send every request through the proxy at `url`, either an http or a
+    /// socks5 proxy. When it isn't set the usual HTTP_PROXY, HTTPS_PROXY and
+    /// NO_PROXY variables are used
+    pub fn proxy(mut self, url: Option<String>) -> Self {
+        self.proxy = url;
+        self
+    }
+
+    /// Hosts that are connected to directly instead of through the proxy, 
+    /// separated by commas like NO_PROXY, ex: "localhost,.internal.example.com"
+    pub fn no_proxy(mut self, hosts: Option<String>) -> Self {
+        self.no_proxy = hosts;
+        self
+    }
+
+    /// Trust the certificates in these PEM files on top of the usual ones, 
+    /// for proxies that intercept TLS with their own certificate
+    pub fn root_certificates(mut self, paths: Vec<PathBuf>) -> Self {
+        self.root_certificates = paths;
+        self
+    }
+
+    /// Make connections with the os's TLS or with rustls
+    pub fn tls(mut self, tls: TlsBackend) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Use a different download cache than the one in the user's cache directory
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Never touch the network, every request is answered with the response 
+    /// that was cached the last time it was made and fails if there isn't one
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Skip the cache and fetch every response from the api again
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// How long a cached response is used before checking if it's changed
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Most requests to have in flight at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How failed requests are retried
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Report what's happening while mods are resolved, downloaded and installed to `progress`
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = Box::new(progress);
+        self
+    }
+
+    /// Build the http client, fails if the proxy, certificates or token aren't valid
+    pub fn build(self) -> Result<Minebrew> {
+        let mut client = Client::builder().user_agent(&self.user_agent);
+        client = match self.tls {
+            TlsBackend::Native => client.use_native_tls(),
+            TlsBackend::Rustls => client.use_rustls_tls(),
+        };
+
+        for path in &self.root_certificates {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Error reading \"{}\"", path.display()))?;
+            let certs = Certificate::from_pem_bundle(&pem).ok()
+                .filter(|certs| !certs.is_empty())
+                .with_context(|| format!("\"{}\" doesn't hold any PEM certificates", path.display()))?;
+            for cert in certs {
+                client = client.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy).with_context(|| format!("Error with proxy \"{proxy}\""))?
+                .no_proxy(self.no_proxy.as_deref().and_then(NoProxy::from_string));
+            // the proxy variables mustn't send hosts in no_proxy through another proxy
+            client = client.no_proxy().proxy(proxy);
+        }
+
+        let token = match &self.token {
+            Some(token) => {
+                let mut value = HeaderValue::from_str(token.trim()).context("The modrinth token isn't valid")?;
+                value.set_sensitive(true);
+                Some(value)
+            },
+            None => None,
+        };
+
+        Ok(Minebrew {
+            client: client.build().context("Error setting up the http client")?,
+            api_url: self.api_url,
+            token,
+            cache: self.cache,
+            offline: self.offline,
+            refresh: self.refresh,
+            ttl: self.ttl,
+            concurrency: self.concurrency,
+            timeout: self.timeout,
+            retry: self.retry,
+            rate_limited_until: Mutex::new(None),
+            progress: self.progress,
+        })
     }
 }
 
 impl Minebrew {
-    /// Take a reference to a search struct and return a 
-    /// Vec of Search Responses wrapped in a Result
-    pub async fn search<'a>(&self, search: &'a Search<'a>) -> Vec<SearchResponse<'a>> {
+    /// Make requests against a modrinth compatible api other than modrinth.com, 
+    /// use `Minebrew::builder` to set anything else
+    pub fn new(api_url: &str) -> Self {
+        Self::builder()
+            .api_url(api_url)
+            .build()
+            .expect("the default http client should always build")
+    }
+
+    pub fn builder() -> MinebrewBuilder {
+        MinebrewBuilder::default()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Make a GET request, waiting for the rate limit to reset first if it's 
+    /// run out. Dropped connections, timeouts, 5xx and 429 responses are 
+    /// retried with exponential backoff, the last response is returned as is
+    async fn send(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        self.send_with(Method::GET, url, headers, None).await
+    }
+
+    /// Same as `send` for any method, with `body` sent as json
+    async fn send_with(&self, method: Method, url: &str, headers: HeaderMap, body: Option<&serde_json::Value>) -> Result<Response> {
+        self.send_then(method, url, headers, body, |resp| async move { Ok(resp) }).await
+    }
+
+    /// Same as `send_with`, then `finish` reads what it needs from the 
+    /// response. The timeout covers `finish` too, and a response that 
+    /// breaks off while it's being read is requested again
+    async fn send_then<T, F, Fut>(&self, method: Method, url: &str, headers: HeaderMap, body: Option<&serde_json::Value>, finish: F) -> Result<T>
+    where
+        F: Fn(Response) -> Fut,
+        Fut: Future<Output = reqwest::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let until = *self.rate_limited_until.lock().unwrap();
+            if let Some(until) = until {
+                tokio::time::sleep_until(until).await;
+            }
+
+            let mut request = self.client.request(method.clone(), url).headers(headers.clone());
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            if let Some(token) = self.token.as_ref().filter(|_| url.starts_with(&self.api_url)) {
+                request = request.header(AUTHORIZATION, token.clone());
+            }
+            let sent = tokio::time::timeout(self.timeout, async {
+                let resp = request.send().await?;
+                if let Some(reset) = rate_limit_reset(resp.headers()) {
+                    *self.rate_limited_until.lock().unwrap() = Some(Instant::now() + reset);
+                }
+                match self.retry.delay_for(attempt, resp.status(), resp.headers()) {
+                    Some(delay) => Ok(ControlFlow::Continue(delay)),
+                    None => finish(resp).await.map(ControlFlow::Break),
+                }
+            }).await;
+
+            let delay = match sent {
+                Ok(Ok(ControlFlow::Break(value))) => return Ok(value),
+                Ok(Ok(ControlFlow::Continue(delay))) => delay,
+                Ok(Err(e)) if (e.is_connect() || e.is_timeout() || e.is_request() || e.is_body())
+                    && attempt < self.retry.retries => self.retry.backoff(attempt),
+                Ok(Err(e)) => return Err(e).with_context(|| format!("Error requesting {url}")),
+                Err(_) if attempt < self.retry.retries => self.retry.backoff(attempt),
+                Err(_) => bail!("{url} didn't respond within {}s", self.timeout.as_secs()),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Make a GET request against the api, returns `None` on a 404. Every 
+    /// response is kept in the cache and reused until it's older than the 
+    /// ttl, after that it's revalidated with a conditional request
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<Option<T>> {
+        self.request_json(url, None).await
+    }
+
+    /// Same as `get_json`, but POSTs `body` when there is one. 
+    /// Responses are cached by the url and the body together
+    async fn request_json<T: DeserializeOwned>(&self, url: &str, body: Option<serde_json::Value>) -> Result<Option<T>> {
+        let key = match &body {
+            Some(body) => format!("{url} {body}"),
+            None => url.to_string(),
+        };
+        let cached = self.cache.response(&key);
+
+        let body = match cached {
+            Some(cached) if self.offline => cached.body,
+            None if self.offline => bail!("{url} hasn't been fetched before, so it isn't available offline"),
+            Some(cached) if !self.refresh && cached.age() < self.ttl => cached.body,
+            cached => {
+                let cached = cached.filter(|_| !self.refresh);
+                let mut headers = HeaderMap::new();
+                if let Some(cached) = &cached {
+                    if let Some(etag) = cached.etag.as_ref().and_then(|e| HeaderValue::from_str(e).ok()) {
+                        headers.insert(IF_NONE_MATCH, etag);
+                    }
+                    if let Some(modified) = cached.last_modified.as_ref().and_then(|m| HeaderValue::from_str(m).ok()) {
+                        headers.insert(IF_MODIFIED_SINCE, modified);
+                    }
+                }
+
+                let method = if body.is_some() { Method::POST } else { Method::GET };
+                let resp = self.send_then(method, url, headers, body.as_ref(), |resp| async move {
+                    let header = |name| resp.headers().get(name)
+                        .and_then(|v: &HeaderValue| v.to_str().ok())
+                        .map(str::to_string);
+                    let (status, etag, last_modified) = (resp.status(), header(ETAG), header(LAST_MODIFIED));
+
+                    // there's only a body worth reading when there's something new
+                    let body = match status {
+                        StatusCode::NOT_FOUND | StatusCode::NOT_MODIFIED => String::new(),
+                        _ => resp.error_for_status()?.text().await?,
+                    };
+                    Ok(ApiResponse { status, etag, last_modified, body })
+                }).await?;
+
+                // not being able to cache a response shouldn't stop anything
+                match (resp.status, cached) {
+                    (StatusCode::NOT_FOUND, _) => return Ok(None),
+                    (StatusCode::NOT_MODIFIED, Some(mut cached)) => {
+                        cached.revalidated();
+                        self.cache.store_response(&cached).ok();
+                        cached.body
+                    },
+                    _ => {
+                        let fresh = CachedResponse::new(&key, resp.body, resp.etag, resp.last_modified);
+                        self.cache.store_response(&fresh).ok();
+                        fresh.body
+                    },
+                }
+            },
+        };
+
+        Ok(Some(serde_json::from_str(&body).with_context(|| format!("Error reading the response from {url}"))?))
+    }
+
+    /// Take a reference to a search struct and return the 
+    /// response for each of its queries
+    pub async fn search<'a>(&self, search: &'a Search<'a>) -> Vec<Result<SearchResponse<'a>>> {
         // start all requests asyncronously
-        stream::iter(search.urls()).map(|(u, q)| async {
-            let resp = tokio::spawn(self.client.get(u).send()).await.unwrap().unwrap();
-            let mut s_resp = tokio::spawn(resp.json::<SearchResponse>()).await.unwrap().unwrap();
+        stream::iter(search.urls(&self.api_url)).map(|(u, q)| async move {
+            let mut s_resp = self.get_json::<SearchResponse>(&u).await
+                .and_then(|r| r.ok_or_else(|| anyhow!("the api has no search endpoint")))
+                .with_context(|| format!("Error searching for \"{q}\""))?;
             s_resp.set_query(q);
-            s_resp
-        }).buffer_unordered(search.queries.len()).collect().await
+            Ok(s_resp)
+        }).buffer_unordered(self.concurrency).collect().await
+    }
+
+    /// Fetch a single page of search results for one of a search's 
+    /// queries, used to load more results than the first page
+    pub async fn search_page<'a>(&self, search: &Search<'a>, query: &'a str, offset: usize) -> Result<SearchResponse<'a>> {
+        let mut s_resp = self.get_json::<SearchResponse>(&search.url(&self.api_url, query, offset)).await
+            .and_then(|r| r.ok_or_else(|| anyhow!("the api has no search endpoint")))
+            .with_context(|| format!("Error searching for \"{query}\""))?;
+        s_resp.set_query(query);
+        Ok(s_resp)
+    }
+
+    /// Look up a project directly by its id or slug, returns `None` if 
+    /// modrinth doesn't know about it, or it isn't cached when offline
+    pub async fn project(&self, id: &str) -> Result<Option<Project>> {
+        let url = format!("{}/project/{}", self.api_url, id);
+        if self.offline && self.cache.response(&url).is_none() {
+            return Ok(None);
+        }
+
+        self.get_json::<Project>(&url).await
+    }
+
+    /// Look up several projects by their ids or slugs in one request, 
+    /// any modrinth doesn't know about are left out
+    pub async fn projects(&self, ids: &[String]) -> Result<Vec<Project>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!("{}/projects?ids={}", self.api_url, serde_json::to_string(ids)?);
+        Ok(self.get_json::<Vec<Project>>(&url).await
+            .context("Error fetching projects")?
+            .unwrap_or_default())
+    }
+
+    /// Every version of a project, newest first. Only versions for one of 
+    /// `loaders` and one of `game_versions` are returned, an empty list 
+    /// doesn't filter. `None` when modrinth doesn't know about the project
+    pub async fn project_versions(&self, id: &str, loaders: &[String], game_versions: &[String]) -> Result<Option<Vec<Version>>> {
+        let mut params = Vec::new();
+        if !loaders.is_empty() {
+            params.push(format!("loaders={}", serde_json::to_string(loaders)?));
+        }
+        if !game_versions.is_empty() {
+            params.push(format!("game_versions={}", serde_json::to_string(game_versions)?));
+        }
+
+        let mut url = format!("{}/project/{}/version", self.api_url, id);
+        if !params.is_empty() {
+            url.push('?');
+            url.push_str(&params.join("&"));
+        }
+
+        self.get_json::<Vec<Version>>(&url).await
+            .with_context(|| format!("Error fetching the versions of {id}"))
+    }
+
+    /// Look up a single version by its id
+    pub async fn version(&self, id: &str) -> Result<Option<Version>> {
+        self.get_json::<Version>(&format!("{}/version/{}", self.api_url, id)).await
+            .with_context(|| format!("Error fetching version {id}"))
+    }
+
+    /// Look up several versions by their ids in one request, 
+    /// any modrinth doesn't know about are left out
+    pub async fn versions(&self, ids: &[String]) -> Result<Vec<Version>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!("{}/versions?ids={}", self.api_url, serde_json::to_string(ids)?);
+        Ok(self.get_json::<Vec<Version>>(&url).await
+            .context("Error fetching versions")?
+            .unwrap_or_default())
+    }
+
+    /// Find the version a file belongs to from its hash
+    pub async fn version_from_hash(&self, hash: &str, algorithm: HashAlgorithm) -> Result<Option<Version>> {
+        let url = format!("{}/version_file/{}?algorithm={}", self.api_url, hash, algorithm.as_str());
+        self.get_json::<Version>(&url).await
+            .with_context(|| format!("Error looking up the file with hash {hash}"))
+    }
+
+    /// Find the versions several files belong to in one request, 
+    /// keyed by hash. Hashes modrinth doesn't know are left out
+    pub async fn versions_from_hashes(&self, hashes: &[String], algorithm: HashAlgorithm) -> Result<HashMap<String, Version>> {
+        if hashes.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let url = format!("{}/version_files", self.api_url);
+        let body = json!({ "hashes": hashes, "algorithm": algorithm.as_str() });
+        Ok(self.request_json::<HashMap<String, Version>>(&url, Some(body)).await
+            .context("Error looking up files by their hashes")?
+            .unwrap_or_default())
+    }
+
+    /// Every minecraft version modrinth knows about, newest first. 
+    /// Used to expand version ranges into exact versions
+    pub async fn game_versions(&self) -> Result<Vec<GameVersionTag>> {
+        self.get_json::<Vec<GameVersionTag>>(&format!("{}/tag/game_version", self.api_url)).await
+            .context("Error fetching the list of minecraft versions")?
+            .context("the api has no list of minecraft versions")
+    }
+
+    /// Find a version to install for each project slug, mods that 
+    /// have been pinned in `state` will only ever resolve to their pin. 
+    /// Only versions at least as stable as `channel` are considered. 
+    /// Every mod that can't be resolved is listed in the error
+    pub async fn files_from_slugs(&self, slugs: &[String], versions: &[String], loaders: &[String], channel: VersionType, state: &State) -> Result<Vec<Candidate>> {
+        let candidates = stream::iter(slugs).map(|slug| async move {
+            self.progress.resolve_started(slug);
+            let vers = self.project_versions(slug, loaders, versions).await?
+                .ok_or_else(|| anyhow!("{slug} not found"))?;
+            Version::select(slug, vers, state.pin(slug), channel)
+        }).buffer_unordered(self.concurrency).collect::<Vec<_>>().await;
+
+        let mut found = Vec::with_capacity(candidates.len());
+        let mut errors = Vec::new();
+        for candidate in candidates {
+            match candidate {
+                Ok(c) => found.push(c),
+                Err(e) => errors.push(format!("{e:#}")),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(errors.join("\n"));
+        }
+        Ok(found)
     }
 
-    pub async fn files_from_results(&self, results: &[SearchResult], version: &str) -> Vec<ModFile> {
-        stream::iter(results.into_iter()).map(|res| async {
-            let url = format!("https://api.modrinth.com/v2/project/{}/version?game_versions=[\"{}\"]", res.slug, version);
-            let resp = tokio::spawn(self.client.get(url).send()).await.unwrap().unwrap();
+    /// Make sure none of the candidates require a version of a mod that's 
+    /// pinned to a different one, every conflict is listed in the error
+    pub async fn check_dependency_pins(&self, candidates: &[Candidate], state: &State) -> Result<()> {
+        // only dependencies on an exact version can conflict with a pin
+        let required: Vec<(&Candidate, &str)> = candidates.iter()
+            .flat_map(|c| c.version.dependencies.iter()
+                .filter(|d| d.dependency_type == DependencyType::Required)
+                .filter_map(move |d| d.version_id.as_deref().map(|id| (c, id))))
+            .collect();
+        if required.is_empty() || state.pins.is_empty() {
+            return Ok(());
+        }
 
-            let mut vers = tokio::spawn(resp.json::<Vec<Version>>()).await.unwrap().unwrap();
-            // just removing the first item in the vector
-            let mut ver = vers.remove(0);
+        let mut ids: Vec<String> = required.iter().map(|(_, id)| id.to_string()).collect();
+        ids.sort();
+        ids.dedup();
+        let versions = self.versions(&ids).await?;
 
-            match ver.files.iter().enumerate().find(|(_, f)| f.primary) {
-                Some((i, _)) => ver.files.swap_remove(i),
-                None => ver.files.swap_remove(0),
+        let mut project_ids: Vec<String> = versions.iter().map(|v| v.project_id.clone()).collect();
+        project_ids.sort();
+        project_ids.dedup();
+        let projects = self.projects(&project_ids).await?;
+
+        let mut conflicts = Vec::new();
+        for (c, id) in required {
+            let dependency = versions.iter()
+                .find(|v| v.id == id)
+                .and_then(|v| projects.iter().find(|p| p.id == v.project_id).map(|p| (v, p)));
+
+            if let Some((version, project)) = dependency {
+                match state.pin(&project.slug) {
+                    Some(pin) if !version.matches(&pin.version_id) => conflicts.push(format!(
+                        "{} {} requires {} {}, but {} is pinned to {}",
+                        c.slug, c.version.version_number, project.slug, version.version_number, project.slug, pin.version_number
+                    )),
+                    _ => {},
+                }
             }
-        }).buffer_unordered(results.len()).collect().await
+        }
+
+        if !conflicts.is_empty() {
+            bail!(conflicts.join("\n"));
+        }
+        Ok(())
     }
 
-    pub async fn download_files(&self, files: &[ModFile], download_dir: &Path) -> Result<()> {
-        let mut handles = Vec::with_capacity(files.len());
+    /// Fetch exactly the version of a mod that was asked for with `slug@version`, 
+    /// `requested` can be either a version number or a version id
+    pub async fn exact_version(&self, slug: &str, requested: &str, targets: &[String], loaders: &[String], state: &State) -> Result<Candidate> {
+        self.progress.resolve_started(slug);
+        let mut vers = match self.project_versions(slug, &[], &[]).await? {
+            Some(vers) => vers,
+            None => bail!("{slug} not found"),
+        };
+        let mut version = match vers.iter().position(|v| v.matches(requested)) {
+            Some(i) => vers.swap_remove(i),
+            None => bail!("{slug} has no version {requested}"),
+        };
+
+        if let Some(pin) = state.pin(slug) {
+            if !version.matches(&pin.version_id) {
+                bail!("{slug} is pinned to {}, run `mbrew unpin {slug}` before installing {requested}", pin.version_number);
+            }
+        }
 
-        let mut total_bytes = 0_usize;
+        version.check_compatible(slug, targets, loaders)?;
+
+        let file = version.primary_file();
+        Ok(Candidate { slug: slug.to_string(), version, file, held_back: None })
+    }
+
+    /// Download files into `download_dir`, files that are already in the 
+    /// cache are linked from there instead. Every download is checked against 
+    /// the hash modrinth has for it and added to the cache, interrupted 
+    /// downloads are resumed. When offline every file has to be in the cache already
+    pub async fn download_files(&self, files: &[&ModFile], download_dir: &Path) -> Result<()> {
+        if self.offline {
+            let missing: Vec<&str> = files.iter()
+                .filter(|f| self.cache.get(&f.hashes.sha512).is_none())
+                .map(|f| f.filename.as_str())
+                .collect();
+            if !missing.is_empty() {
+                bail!("these files haven't been downloaded before, so they aren't available offline: {}", missing.join(", "));
+            }
+        }
+
+        let mut to_download = Vec::with_capacity(files.len());
         for file in files {
-            let handle = tokio::spawn(self.client.get(&file.url).send());
-            handles.push((&file.filename, handle));
-            total_bytes += file.size;
+            if let Some(cached) = self.cache.get(&file.hashes.sha512) {
+                let dest = download_dir.join(&file.filename);
+                self.cache.install(&cached, &dest)?;
+                self.progress.install(&file.filename, &dest, true);
+                continue;
+            }
+
+            to_download.push(*file);
         }
 
-        let mut downloaded = 0_usize;
-        for (filename, handle) in handles {
-            let resp = handle.await??;
-            let mut byte_stream = resp.bytes_stream();
-            let mut file = std::fs::File::create(download_dir.join(filename))?;
+        stream::iter(to_download)
+            .map(|file| self.download_file(file, download_dir))
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>().await
+            .into_iter()
+            .collect()
+    }
+
+    /// Download a single file into the cache and install it. A partial 
+    /// download left in staging by an earlier try is resumed rather than started over
+    async fn download_file(&self, mod_file: &ModFile, download_dir: &Path) -> Result<()> {
+        let staging = self.cache.staging_path(&mod_file.hashes.sha512, &mod_file.filename)?;
+
+        let resumed = self.fetch_into(mod_file, &staging).await?;
+        self.progress.verify(&mod_file.filename);
+        let mut sha512 = hash_file(&staging)?;
+
+        // what was left in staging might not have been the start of this 
+        // file at all, so a resumed download gets one more try from scratch
+        if resumed && !mod_file.hashes.sha512.is_empty() && sha512 != mod_file.hashes.sha512 {
+            std::fs::remove_file(&staging)?;
+            self.fetch_into(mod_file, &staging).await?;
+            self.progress.verify(&mod_file.filename);
+            sha512 = hash_file(&staging)?;
+        }
+
+        if !mod_file.hashes.sha512.is_empty() && sha512 != mod_file.hashes.sha512 {
+            std::fs::remove_file(&staging).ok();
+            bail!("{} is corrupt, its sha512 hash doesn't match the one modrinth has", mod_file.filename);
+        }
+
+        let cached = self.cache.insert(&sha512, &mod_file.filename, &staging)?;
+        let dest = download_dir.join(&mod_file.filename);
+        self.cache.install(&cached, &dest)?;
+        self.progress.install(&mod_file.filename, &dest, false);
+        Ok(())
+    }
+
+    /// Download a file into `staging`, picking up from the end of whatever is 
+    /// already there with a Range request. Servers that don't support ranges 
+    /// send the whole file, which replaces it. A connection that drops part way 
+    /// is resumed the same way, returns whether any of the file was kept
+    async fn fetch_into(&self, mod_file: &ModFile, staging: &Path) -> Result<bool> {
+        let mut kept = false;
+        let mut attempt = 0;
+        loop {
+            let have = file_len(staging);
+            if have > 0 && have as usize >= mod_file.size && mod_file.size > 0 {
+                return Ok(true);
+            }
+
+            let mut headers = HeaderMap::new();
+            if have > 0 {
+                headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={have}-"))?);
+            }
+            let resp = self.send(&mod_file.url, headers).await?;
+
+            // what's in staging is no use, start again without it
+            if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                std::fs::remove_file(staging)?;
+                continue;
+            }
 
-            while let Some(item) = byte_stream.next().await {
-                let chunk = item?;
-                downloaded += chunk.len();
-                file.write_all(&chunk)?;
+            let resp = resp.error_for_status()?;
+            let resuming = have > 0 
+                && resp.status() == StatusCode::PARTIAL_CONTENT 
+                && range_start(resp.headers()) == Some(have);
 
-                let percent = (downloaded as f32)/(total_bytes as f32);
-                let progress = (percent * 30.0) as usize;
-                let space = 30 - progress;
-                let bar = format!("[{:#<p_width$}{: <s_width$}] {}%", "", "", (percent * 100.0) as usize, p_width=progress, s_width = space);
+            let (mut file, start) = if resuming {
+                kept = true;
+                (std::fs::OpenOptions::new().append(true).open(staging)?, have)
+            } else {
+                (std::fs::File::create(staging)?, 0)
+            };
+            self.progress.file_started(&mod_file.filename, mod_file.size as u64, start);
 
-                print!("\x1B[2K\x1B[60DDownloading...\t{}", bar);
-                std::io::stdout().flush();
+            match self.stream_into(resp, &mut file, &mod_file.filename, start).await {
+                Ok(()) => {
+                    self.progress.file_finished(&mod_file.filename);
+                    return Ok(kept);
+                },
+                Err(_) if attempt < self.retry.retries => {
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                },
+                Err(e) => return Err(e).with_context(|| format!(
+                    "Error downloading {}, run the command again to resume it", mod_file.filename)),
             }
         }
+    }
+
+    /// Write the body of `resp` to `file` as it arrives, `start` bytes are 
+    /// already in the file. Gives up if nothing arrives for as long as the timeout
+    async fn stream_into(&self, resp: Response, file: &mut std::fs::File, filename: &str, start: u64) -> Result<()> {
+        let mut byte_stream = resp.bytes_stream();
+        let mut downloaded = start;
 
+        loop {
+            let item = match tokio::time::timeout(self.timeout, byte_stream.next()).await {
+                Ok(Some(item)) => item,
+                Ok(None) => break,
+                Err(_) => bail!("the download stalled for {}s", self.timeout.as_secs()),
+            };
 
+            let chunk = item?;
+            file.write_all(&chunk)?;
+            downloaded += chunk.len() as u64;
+            self.progress.file_progress(filename, downloaded);
+        }
+
+        file.flush()?;
         Ok(())
     }
 }
+
+/// Size of a file in bytes, 0 if it doesn't exist
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+/// The sha512 hash of a file as hex
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("Error reading \"{}\"", path.display()))?;
+    let mut hasher = Sha512::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Where the body of a 206 response starts, from 
+/// its Content-Range header, ex: "bytes 100-199/200"
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_RANGE)?
+        .to_str().ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse().ok()
+}
+
+#[cfg(test)]
+mod minebrew_tests {
+    use super::*;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use super::super::version::Hashes;
+    use super::super::shared::{DependencyType, ProjectStatus, VersionStatus};
+    use crate::state::Pin;
+
+    /// A Minebrew talking to `server` that retries without waiting
+    fn test_client(server: &MockServer, name: &str) -> Minebrew {
+        test_builder(server, name).build().unwrap()
+    }
+
+    /// A builder for a Minebrew talking to `server` with a cache of its own
+    fn test_builder(server: &MockServer, name: &str) -> MinebrewBuilder {
+        let dir = std::env::temp_dir().join(format!("minebrew-client-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        Minebrew::builder()
+            .api_url(&server.uri())
+            .cache(Cache::at(dir))
+            .retry(RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() })
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(1)
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "retry");
+        assert!(mbrew.game_versions().await.unwrap().is_empty());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(4)
+            .mount(&server).await;
+        // client errors won't change by asking again
+        Mock::given(method("GET")).and(path("/project/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "give-up");
+        assert!(mbrew.game_versions().await.is_err());
+        assert!(mbrew.project("missing").await.unwrap().is_none());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]").set_delay(Duration::from_secs(5)))
+            .mount(&server).await;
+
+        let mbrew = test_builder(&server, "timeout")
+            .timeout(Duration::from_millis(50))
+            .retry(RetryPolicy { retries: 1, base_delay: Duration::from_millis(1), ..RetryPolicy::default() })
+            .build().unwrap();
+        let err = mbrew.game_versions().await.unwrap_err();
+        assert!(format!("{err:#}").contains("didn't respond"));
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_body_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // the first response stalls half way, the second breaks off, the third is whole
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 2\r\n\r\n";
+            let mut stalled = Vec::new();
+            for attempt in 0.. {
+                let Ok((mut conn, _)) = listener.accept().await else { break };
+                // a GET without a body fits in one read
+                let mut request = [0u8; 1024];
+                let _ = conn.read(&mut request).await;
+                match attempt {
+                    // half the body then nothing, only the timeout ends this one
+                    0 => {
+                        conn.write_all(format!("{head}[").as_bytes()).await.ok();
+                        stalled.push(conn);
+                    },
+                    // half the body then the connection closes
+                    1 => { conn.write_all(format!("{head}[").as_bytes()).await.ok(); },
+                    _ => { conn.write_all(format!("{head}[]").as_bytes()).await.ok(); },
+                }
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("minebrew-client-{}-body-timeout", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let mbrew = Minebrew::builder()
+            .api_url(&api_url)
+            .cache(Cache::at(dir))
+            .timeout(Duration::from_millis(200))
+            .retry(RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() })
+            .build().unwrap();
+        assert!(mbrew.game_versions().await.unwrap().is_empty());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    /// Keeps every event it's told about
+    #[derive(Clone, Default)]
+    struct Recorder(std::sync::Arc<Mutex<Vec<String>>>);
+
+    impl Progress for Recorder {
+        fn file_started(&self, filename: &str, size: u64, resumed_from: u64) {
+            self.0.lock().unwrap().push(format!("started {filename} {resumed_from}/{size}"));
+        }
+        fn file_finished(&self, filename: &str) {
+            self.0.lock().unwrap().push(format!("finished {filename}"));
+        }
+        fn verify(&self, filename: &str) {
+            self.0.lock().unwrap().push(format!("verify {filename}"));
+        }
+        fn install(&self, filename: &str, _dest: &Path, cached: bool) {
+            self.0.lock().unwrap().push(format!("install {filename} {cached}"));
+        }
+    }
+
+    /// A file served at "/file.jar" by the test server
+    fn test_file(server: &MockServer, body: &[u8]) -> ModFile {
+        ModFile {
+            hashes: Hashes { sha512: format!("{:x}", Sha512::digest(body)), sha1: String::new() },
+            url: format!("{}/file.jar", server.uri()),
+            filename: "file.jar".to_string(),
+            primary: true,
+            size: body.len(),
+            file_type: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let server = MockServer::start().await;
+        let body = b"0123456789abcdef";
+        Mock::given(method("GET")).and(path("/file.jar")).and(header("range", "bytes=6-"))
+            .respond_with(ResponseTemplate::new(206)
+                .insert_header("content-range", "bytes 6-15/16")
+                .set_body_bytes(&body[6..]))
+            .expect(1)
+            .mount(&server).await;
+
+        let events = Recorder::default();
+        let mbrew = test_builder(&server, "resume").progress(events.clone()).build().unwrap();
+        let file = test_file(&server, body);
+        std::fs::write(mbrew.cache.staging_path(&file.hashes.sha512, &file.filename).unwrap(), &body[..6]).unwrap();
+
+        let dest = mbrew.cache.root().join("installed");
+        std::fs::create_dir_all(&dest).unwrap();
+        mbrew.download_files(&[&file], &dest).await.unwrap();
+        assert_eq!(std::fs::read(dest.join("file.jar")).unwrap(), body);
+
+        // the second time it's already cached
+        mbrew.download_files(&[&file], &dest).await.unwrap();
+        assert_eq!(*events.0.lock().unwrap(), [
+            "started file.jar 6/16", "finished file.jar", "verify file.jar", 
+            "install file.jar false", "install file.jar true",
+        ]);
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_resume_unsupported() {
+        let server = MockServer::start().await;
+        let body = b"0123456789abcdef";
+        // the range is ignored and the whole file sent back
+        Mock::given(method("GET")).and(path("/file.jar"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+            .expect(1)
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "no-resume");
+        let file = test_file(&server, body);
+        std::fs::write(mbrew.cache.staging_path(&file.hashes.sha512, &file.filename).unwrap(), b"012345").unwrap();
+
+        let dest = mbrew.cache.root().join("installed");
+        std::fs::create_dir_all(&dest).unwrap();
+        mbrew.download_files(&[&file], &dest).await.unwrap();
+        assert_eq!(std::fs::read(dest.join("file.jar")).unwrap(), body);
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_resume_wrong_partial() {
+        let server = MockServer::start().await;
+        let body = b"0123456789abcdef";
+        Mock::given(method("GET")).and(path("/file.jar")).and(header("range", "bytes=6-"))
+            .respond_with(ResponseTemplate::new(206)
+                .insert_header("content-range", "bytes 6-15/16")
+                .set_body_bytes(&body[6..]))
+            .expect(1)
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/file.jar"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+            .expect(1)
+            .mount(&server).await;
+
+        // a partial download that got corrupted
+        let mbrew = test_client(&server, "wrong-partial");
+        let file = test_file(&server, body);
+        std::fs::write(mbrew.cache.staging_path(&file.hashes.sha512, &file.filename).unwrap(), b"zzzzzz").unwrap();
+
+        let dest = mbrew.cache.root().join("installed");
+        std::fs::create_dir_all(&dest).unwrap();
+        mbrew.download_files(&[&file], &dest).await.unwrap();
+        assert_eq!(std::fs::read(dest.join("file.jar")).unwrap(), body);
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_token_only_sent_to_api() {
+        let api = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version")).and(header("authorization", "mrp_secret"))
+            .and(header("user-agent", DEFAULT_USER_AGENT))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(1)
+            .mount(&api).await;
+
+        let cdn = MockServer::start().await;
+        let body = b"jar";
+        Mock::given(method("GET")).and(path("/file.jar"))
+            .and(|req: &wiremock::Request| !req.headers.keys().any(|k| k.as_str() == "authorization"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(&body[..]))
+            .expect(1)
+            .mount(&cdn).await;
+
+        let mbrew = test_builder(&api, "token").token(Some("mrp_secret".to_string())).build().unwrap();
+        mbrew.game_versions().await.unwrap();
+
+        let dest = mbrew.cache.root().join("installed");
+        std::fs::create_dir_all(&dest).unwrap();
+        mbrew.download_files(&[&test_file(&cdn, body)], &dest).await.unwrap();
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        // a plain http proxy is sent the whole url, which the mock server matches by path
+        let proxy = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(1)
+            .mount(&proxy).await;
+
+        let mbrew = test_builder(&proxy, "proxy")
+            .api_url("http://api.modrinth.invalid")
+            .proxy(Some(proxy.uri()))
+            .build().unwrap();
+        mbrew.game_versions().await.unwrap();
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+
+        // hosts in no_proxy are connected to directly, which can't work for this one
+        let mbrew = test_builder(&proxy, "no-proxy")
+            .api_url("http://api.modrinth.invalid")
+            .proxy(Some(proxy.uri()))
+            .no_proxy(Some("localhost,.modrinth.invalid".to_string()))
+            .retry(RetryPolicy { retries: 0, ..RetryPolicy::default() })
+            .build().unwrap();
+        assert!(mbrew.game_versions().await.is_err());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cached_responses() {
+        let server = MockServer::start().await;
+        // anything that sends validators gets told nothing changed
+        Mock::given(method("GET")).and(path("/tag/game_version")).and(header("if-none-match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(200).insert_header("etag", "\"v1\"").set_body_string("[]"))
+            .expect(2)
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "cached");
+        let url = format!("{}/tag/game_version", server.uri());
+        mbrew.game_versions().await.unwrap();
+
+        // a fresh response is used without asking the server
+        mbrew.game_versions().await.unwrap();
+
+        // a stale one is revalidated, and a 304 makes it fresh again
+        let mut cached = mbrew.cache.response(&url).unwrap();
+        cached.fetched -= 60 * 60;
+        mbrew.cache.store_response(&cached).unwrap();
+        mbrew.game_versions().await.unwrap();
+        assert!(mbrew.cache.response(&url).unwrap().age() < DEFAULT_TTL);
+
+        // --refresh fetches it again without sending the validators
+        let refresh = Minebrew::builder()
+            .api_url(&server.uri())
+            .cache(Cache::at(mbrew.cache.root()))
+            .refresh(true)
+            .build().unwrap();
+        refresh.game_versions().await.unwrap();
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    /// A SOCKS5 proxy that sends every connection to `target`, whatever 
+    /// host it was asked for, returns the proxy's url
+    async fn socks_proxy(target: std::net::SocketAddr) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("socks5h://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    // greeting: version, number of methods, methods. No authentication
+                    let mut head = [0u8; 2];
+                    client.read_exact(&mut head).await?;
+                    client.read_exact(&mut vec![0; head[1] as usize]).await?;
+                    client.write_all(&[5, 0]).await?;
+
+                    // request: version, connect, reserved, address type, address, port
+                    let mut req = [0u8; 4];
+                    client.read_exact(&mut req).await?;
+                    let addr_len = match req[3] {
+                        1 => 4,
+                        4 => 16,
+                        _ => client.read_u8().await? as usize,
+                    };
+                    client.read_exact(&mut vec![0; addr_len + 2]).await?;
+
+                    let mut upstream = tokio::net::TcpStream::connect(target).await?;
+                    client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+                    tokio::io::copy_bidirectional(&mut client, &mut upstream).await.map(|_| ())
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_socks_proxy() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/tag/game_version"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(1)
+            .mount(&server).await;
+
+        // with socks5h the proxy looks up the host, so it never has to resolve
+        let mbrew = test_builder(&server, "socks")
+            .api_url(&format!("http://api.modrinth.invalid:{}", server.address().port()))
+            .proxy(Some(socks_proxy(*server.address()).await))
+            .build().unwrap();
+        mbrew.game_versions().await.unwrap();
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[test]
+    fn test_tls_settings() {
+        assert_eq!("rustls".parse::<TlsBackend>().unwrap(), TlsBackend::Rustls);
+        assert!("openssl".parse::<TlsBackend>().is_err());
+        assert!(Minebrew::builder().tls(TlsBackend::Rustls).build().is_ok());
+
+        let not_pem = std::env::temp_dir().join(format!("minebrew-not-a-cert-{}", std::process::id()));
+        std::fs::write(&not_pem, "hello").unwrap();
+        let err = Minebrew::builder().root_certificates(vec![not_pem.clone()]).build().err().unwrap();
+        assert!(err.to_string().contains("doesn't hold any PEM certificates"));
+        std::fs::remove_file(not_pem).ok();
+    }
+
+    #[test]
+    fn test_range_start() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(range_start(&headers), None);
+        headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes 100-199/200"));
+        assert_eq!(range_start(&headers), Some(100));
+    }
+
+    const VERSION: &str = r#"{
+        "id": "IIJJKKLL", "project_id": "AABBCCDD", "author_id": "EEFFGGHH",
+        "name": "Sodium 0.5.3", "version_number": "mc1.20.1-0.5.3",
+        "changelog": "- fixed things", "changelog_url": null,
+        "dependencies": [{ "version_id": null, "project_id": "P7dR8mSH", "file_name": null, "dependency_type": "required" }],
+        "game_versions": ["1.20.1"], "version_type": "release", "loaders": ["fabric"],
+        "featured": true, "status": "listed", "requested_status": null,
+        "date_published": "2023-09-01T00:00:00Z", "downloads": 1000,
+        "files": [{ "hashes": { "sha512": "aa", "sha1": "bb" }, "url": "https://cdn/sodium.jar",
+                    "filename": "sodium.jar", "primary": true, "size": 10, "file_type": null }]
+    }"#;
+
+    #[tokio::test]
+    async fn test_version_endpoints() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/version/IIJJKKLL"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(VERSION))
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/versions")).and(query_param("ids", r#"["IIJJKKLL","gone"]"#))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("[{VERSION}]")))
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/version_file/bb")).and(query_param("algorithm", "sha1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(VERSION))
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/version_file/cc"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "versions");
+        let version = mbrew.version("IIJJKKLL").await.unwrap().unwrap();
+        assert_eq!(version.changelog.as_deref(), Some("- fixed things"));
+        assert_eq!(version.status, Some(VersionStatus::Listed));
+        assert_eq!(version.dependencies[0].dependency_type, DependencyType::Required);
+        assert_eq!(version.dependencies[0].project_id.as_deref(), Some("P7dR8mSH"));
+
+        let versions = mbrew.versions(&["IIJJKKLL".to_string(), "gone".to_string()]).await.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert!(mbrew.versions(&[]).await.unwrap().is_empty());
+
+        assert!(mbrew.version_from_hash("bb", HashAlgorithm::Sha1).await.unwrap().is_some());
+        assert!(mbrew.version_from_hash("cc", HashAlgorithm::Sha512).await.unwrap().is_none());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_versions_from_hashes() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).and(path("/version_files"))
+            .and(body_json(json!({ "hashes": ["aa", "dd"], "algorithm": "sha512" })))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(r#"{{ "aa": {VERSION} }}"#)))
+            .expect(1)
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "hashes");
+        let hashes = ["aa".to_string(), "dd".to_string()];
+        let found = mbrew.versions_from_hashes(&hashes, HashAlgorithm::Sha512).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found["aa"].id, "IIJJKKLL");
+
+        // answered from the cache from then on, even offline
+        let offline = Minebrew::builder().api_url(&server.uri()).cache(Cache::at(mbrew.cache.root())).offline(true).build().unwrap();
+        assert_eq!(offline.versions_from_hashes(&hashes, HashAlgorithm::Sha512).await.unwrap().len(), 1);
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_dependency_pins() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/versions"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("[{VERSION}]")))
+            .mount(&server).await;
+        Mock::given(method("GET")).and(path("/projects"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"[{
+                "id": "AABBCCDD", "slug": "sodium", "title": "Sodium", "description": "",
+                "project_type": "mod", "client_side": "required", "server_side": "unsupported",
+                "game_versions": [], "loaders": ["fabric"]
+            }]"#))
+            .mount(&server).await;
+
+        // a mod that needs exactly sodium mc1.20.1-0.5.3
+        let mut needs_sodium: Version = serde_json::from_str(VERSION).unwrap();
+        needs_sodium.dependencies[0].version_id = Some("IIJJKKLL".to_string());
+        let file = needs_sodium.primary_file();
+        let candidates = [Candidate { slug: "indium".to_string(), version: needs_sodium, file, held_back: None }];
+
+        let mbrew = test_client(&server, "dependency-pins");
+        let mut state = State::default();
+        assert!(mbrew.check_dependency_pins(&candidates, &state).await.is_ok());
+
+        let pin = |id: &str, number: &str| Pin { version_id: id.to_string(), version_number: number.to_string() };
+        state.pins.insert("sodium".to_string(), pin("IIJJKKLL", "mc1.20.1-0.5.3"));
+        assert!(mbrew.check_dependency_pins(&candidates, &state).await.is_ok());
+
+        state.pins.insert("sodium".to_string(), pin("YL57xq9U", "mc1.20.1-0.5.0"));
+        let e = mbrew.check_dependency_pins(&candidates, &state).await.unwrap_err();
+        assert_eq!(e.to_string(), "indium mc1.20.1-0.5.3 requires sodium mc1.20.1-0.5.3, but sodium is pinned to mc1.20.1-0.5.0");
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_project() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).and(path("/project/sodium"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{
+                "id": "AABBCCDD", "slug": "sodium", "title": "Sodium", "description": "fast",
+                "project_type": "mod", "categories": ["optimization"], "client_side": "required",
+                "server_side": "unsupported", "status": "archived", "game_versions": ["1.20.1"],
+                "loaders": ["fabric"], "license": { "id": "LGPL-3.0-only", "name": "", "url": null },
+                "donation_urls": [{ "id": "patreon", "platform": "Patreon", "url": "https://patreon.com/x" }]
+            }"#))
+            .mount(&server).await;
+
+        let mbrew = test_client(&server, "project");
+        let project = mbrew.project("sodium").await.unwrap().unwrap();
+        assert_eq!(project.status, ProjectStatus::Archived);
+        assert_eq!(project.license.unwrap().id, "LGPL-3.0-only");
+        assert_eq!(project.donation_urls[0].platform, "Patreon");
+        assert!(project.gallery.is_empty());
+        std::fs::remove_dir_all(mbrew.cache.root()).ok();
+    }
+}
@@ -1,8 +1,15 @@
+mod matching;
+mod project;
 mod search;
 mod shared;
 mod version;
 mod minebrew;
+mod retry;
 
-pub use search::{Search, SearchResponse, SearchResult};
-pub use version::{Version, ModFile};
-pub use minebrew::Minebrew;
+pub use matching::{Relevance, normalize, levenshtein, relevance, rank};
+pub use project::{Project, parse_project_url, looks_like_slug};
+pub use search::{Search, SearchResponse, SearchResult, Pick};
+pub use shared::*;
+pub use version::{Version, Versions, VersionType, ModFile, Hashes, Dependency, Candidate, parse_query};
+pub use minebrew::{Minebrew, MinebrewBuilder, TlsBackend, MODRINTH_API, DEFAULT_TTL, DEFAULT_USER_AGENT};
+pub use retry::RetryPolicy;
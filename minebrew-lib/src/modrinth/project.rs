@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use super::shared::*;
+
+/// Hosts whose project pages can be pasted in place of a query
+const MODRINTH_HOSTS: [&str; 2] = ["modrinth.com", "www.modrinth.com"];
+
+/// The kinds of project pages on modrinth.com, the first path
+/// segment of a project url
+const PROJECT_PAGES: [&str; 6] = ["mod", "plugin", "modpack", "resourcepack", "shader", "datapack"];
+
+/// A project on modrinth as returned by a direct lookup of its id or slug, 
+/// fields other modrinth compatible apis might leave out have defaults
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Project {
+    pub id: String,
+
+    pub slug: String,
+
+    pub title: String,
+
+    pub description: String,
+
+    /// the long description, in markdown
+    #[serde(default)]
+    pub body: String,
+
+    pub project_type: ProjectType,
+
+    #[serde(default)]
+    pub categories: Vec<String>,
+
+    #[serde(default)]
+    pub additional_categories: Vec<String>,
+
+    pub client_side: Support,
+
+    pub server_side: Support,
+
+    #[serde(default)]
+    pub status: ProjectStatus,
+
+    #[serde(default)]
+    pub requested_status: Option<ProjectStatus>,
+
+    #[serde(default)]
+    pub issues_url: Option<String>,
+
+    #[serde(default)]
+    pub source_url: Option<String>,
+
+    #[serde(default)]
+    pub wiki_url: Option<String>,
+
+    #[serde(default)]
+    pub discord_url: Option<String>,
+
+    #[serde(default)]
+    pub donation_urls: Vec<DonationInfo>,
+
+    #[serde(default)]
+    pub downloads: usize,
+
+    #[serde(default)]
+    pub followers: usize,
+
+    #[serde(default)]
+    pub icon_url: Option<String>,
+
+    #[serde(default)]
+    pub color: Option<u32>,
+
+    #[serde(default)]
+    pub thread_id: Option<String>,
+
+    #[serde(default)]
+    pub monetization_status: Option<String>,
+
+    /// id of the team that owns the project
+    #[serde(default)]
+    pub team: String,
+
+    #[serde(default)]
+    pub moderator_message: Option<ModeratorMessage>,
+
+    #[serde(default)]
+    pub published: String,
+
+    #[serde(default)]
+    pub updated: String,
+
+    #[serde(default)]
+    pub approved: Option<String>,
+
+    #[serde(default)]
+    pub queued: Option<String>,
+
+    #[serde(default)]
+    pub license: Option<License>,
+
+    /// ids of every version of the project
+    #[serde(default)]
+    pub versions: Vec<String>,
+
+    pub game_versions: Vec<String>,
+
+    pub loaders: Vec<String>,
+
+    #[serde(default)]
+    pub gallery: Vec<Picture>,
+}
+
+/// Pulls the slug (and version, if there is one) out of a project url
+/// like `https://modrinth.com/mod/sodium/version/mc1.19.2-0.4.4`
+pub fn parse_project_url(url: &str) -> Option<(&str, Option<&str>)> {
+    let rest = url.strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+
+    // drop any query string or fragment
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+
+    let mut segments = rest.split('/').filter(|s| !s.is_empty());
+    if !MODRINTH_HOSTS.contains(&segments.next()?) || !PROJECT_PAGES.contains(&segments.next()?) {
+        return None;
+    }
+
+    let slug = segments.next()?;
+    match (segments.next(), segments.next()) {
+        (Some("version"), Some(version)) => Some((slug, Some(version))),
+        _ => Some((slug, None)),
+    }
+}
+
+/// Returns true if a query could be a project slug or id, these are
+/// looked up directly before falling back to searching
+pub fn looks_like_slug(query: &str) -> bool {
+    !query.is_empty()
+        && query.len() <= 64
+        && query.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+#[cfg(test)]
+mod project_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_project_url() {
+        assert_eq!(parse_project_url("https://modrinth.com/mod/sodium"), Some(("sodium", None)));
+        assert_eq!(parse_project_url("https://modrinth.com/mod/fabric-api/"), Some(("fabric-api", None)));
+        assert_eq!(parse_project_url("https://www.modrinth.com/shader/iris?tab=versions"), Some(("iris", None)));
+        assert_eq!(
+            parse_project_url("https://modrinth.com/mod/sodium/version/mc1.19.2-0.4.4"),
+            Some(("sodium", Some("mc1.19.2-0.4.4")))
+        );
+
+        assert_eq!(parse_project_url("https://modrinth.com/user/jellysquid3"), None);
+        assert_eq!(parse_project_url("https://example.com/mod/sodium"), None);
+        assert_eq!(parse_project_url("https://modrinth.com/mod"), None);
+        assert_eq!(parse_project_url("sodium"), None);
+    }
+
+    #[test]
+    fn test_looks_like_slug() {
+        assert!(looks_like_slug("fabric-api"));
+        assert!(looks_like_slug("AANobbMI"));
+        assert!(!looks_like_slug("fabric api"));
+        assert!(!looks_like_slug(""));
+    }
+}
@@ -0,0 +1,117 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+use std::time::Duration;
+
+// When a request fails in a way that might not happen the next time, a dropped
+// connection, a timeout, a 5xx or a 429 from being rate limited, it's tried
+// again after waiting twice as long as the time before. When the api says how
+// long to wait, with Retry-After or modrinth's X-Ratelimit-Reset, it's listened to
+
+/// How failed requests are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// how many times a request is tried again after the first try
+    pub retries: u32,
+
+    /// wait before the first retry, doubled for every retry after it
+    pub base_delay: Duration,
+
+    /// the longest to ever wait between tries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retry number `attempt`, counting from 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+
+    /// How long to wait before trying again after getting a response with
+    /// `status` on try number `attempt`, `None` if it shouldn't be retried
+    pub fn delay_for(&self, attempt: u32, status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+        if attempt >= self.retries || !retryable(status) {
+            return None;
+        }
+
+        let told = retry_after(headers).or_else(|| rate_limit_reset(headers));
+        Some(told.unwrap_or_else(|| self.backoff(attempt)).min(self.max_delay))
+    }
+}
+
+/// Responses that might be different if the request is made again
+pub fn retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// How long modrinth says to wait until the rate limit resets,
+/// only when there are no requests left before then
+pub fn rate_limit_reset(headers: &HeaderMap) -> Option<Duration> {
+    let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+
+    match number("x-ratelimit-remaining")? {
+        0 => number("x-ratelimit-reset").map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+/// The number of seconds in a Retry-After header,
+/// the http date form isn't supported
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers.get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse::<u64>().ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(20), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_delay_for() {
+        let policy = RetryPolicy::default();
+        let none = HeaderMap::new();
+
+        assert_eq!(policy.delay_for(0, StatusCode::BAD_GATEWAY, &none), Some(Duration::from_millis(500)));
+        assert_eq!(policy.delay_for(0, StatusCode::NOT_FOUND, &none), None);
+        assert_eq!(policy.delay_for(3, StatusCode::BAD_GATEWAY, &none), None);
+
+        // the api knows best how long to wait
+        let told = headers(&[("retry-after", "7")]);
+        assert_eq!(policy.delay_for(0, StatusCode::TOO_MANY_REQUESTS, &told), Some(Duration::from_secs(7)));
+
+        let limited = headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "12")]);
+        assert_eq!(policy.delay_for(1, StatusCode::TOO_MANY_REQUESTS, &limited), Some(Duration::from_secs(12)));
+        assert_eq!(rate_limit_reset(&headers(&[("x-ratelimit-remaining", "40"), ("x-ratelimit-reset", "12")])), None);
+    }
+}
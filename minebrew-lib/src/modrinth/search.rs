@@ -1,8 +1,9 @@
 use std::io::Write;
 
 use super::shared::*;
+use super::matching;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// A struct used to build a query and then run a search 
 /// for a query and then handle the result
@@ -16,29 +17,61 @@ pub struct Search <'a> {
     /// sorting method for search results
     pub index: &'a str,
 
-    /// The version to filter minecraft searches by
-    pub version: &'a str,
+    /// The minecraft versions to filter searches by, any of them will do
+    pub versions: &'a [String],
+
+    /// The kind of project to search for
+    pub project_type: ProjectType,
+
+    /// The loaders to filter searches by, any of them will do
+    pub loaders: Vec<String>,
+
+    /// Only show projects that run on this side
+    pub side: Option<Side>,
 }
 
 impl <'a> Search <'a> {
     /// Construct a search to make on the modrinth database
-    pub fn new(queries: &[String], version: &'a str) -> Self {
+    pub fn new(queries: &[String], versions: &'a [String], project_type: ProjectType, loaders: Vec<String>) -> Self {
         Self {
             queries: queries.to_vec(),
             limit: 5,
             index: "relevance",
-            version, 
+            versions, 
+            project_type,
+            loaders,
+            side: None,
         }
     }
 
-    /// Returns an iterator over the urls each of which is a search 
-    /// on the modrinth database
-    pub fn urls(&self) -> impl Iterator<Item=(String, &str)> + '_ {
-        self.queries.iter().map(|q| {
-            (format!(
-                "https://api.modrinth.com/v2/search?query={}&limit={}&index={}&facets=[[\"versions:{}\"]]",
-                q, self.limit, self.index, self.version), q.as_str())
-        })
+    /// Returns the urls each of which is a search 
+    /// on the modrinth database at `api_url`
+    pub fn urls(&self, api_url: &str) -> Vec<(String, &str)> {
+        self.queries.iter().map(|q| (self.url(api_url, q, 0), q.as_str())).collect()
+    }
+
+    /// The url of a single page of search results for a query, 
+    /// starting `offset` results in
+    pub fn url(&self, api_url: &str, query: &str, offset: usize) -> String {
+        // facets in the same list are OR'd, separate lists are AND'd
+        let versions: Vec<String> = self.versions.iter()
+            .map(|v| format!("\"versions:{}\"", v))
+            .collect();
+        let mut facets = format!("[{}],[\"project_type:{}\"]", versions.join(","), self.project_type);
+        if !self.loaders.is_empty() {
+            let loaders: Vec<String> = self.loaders.iter()
+                .map(|l| format!("\"categories:{}\"", l))
+                .collect();
+            facets.push_str(&format!(",[{}]", loaders.join(",")));
+        }
+        if let Some(side) = self.side {
+            facets.push(',');
+            facets.push_str(side.facet());
+        }
+
+        format!(
+            "{}/search?query={}&limit={}&offset={}&index={}&facets=[{}]",
+            api_url, query, self.limit, offset, self.index, facets)
     }
 }
 
@@ -47,17 +80,11 @@ impl <'a> Search <'a> {
 pub struct SearchResponse<'a> {
     pub hits: Vec<SearchResult>,
 
-    #[serde(skip)]
-    #[serde(rename = "offset")]
-    _offset: u8,
+    pub offset: usize,
 
-    #[serde(skip)]
-    #[serde(rename = "limit")]
-    _limit: u8,
+    pub limit: usize,
 
-    #[serde(skip)]
-    #[serde(rename = "total_hits")]
-    _total_hits: u8,
+    pub total_hits: usize,
 
     #[serde(skip)]
     query: Option<&'a str>
@@ -69,84 +96,119 @@ impl <'a> SearchResponse <'a> {
         self.query = Some(query);
     }
 
-    /// Function that takes a query and an lienence value and filters 
-    /// out any search result that isnt withint a `lenience` levenshtein 
-    /// distance
-    pub fn filter(&mut self, lenience: usize) {
+    /// Function to get the query that the SearchResponse was generated from
+    pub fn query(&self) -> &'a str {
+        self.query.unwrap()
+    }
+
+    /// Function that drops any search result that doesn't match the query 
+    /// and sorts the rest best match first, see the `matching` module
+    pub fn rank(&mut self) {
         let query = self.query.unwrap();
-        self.hits.retain(|res| {
-            levenshtein(query, &res.title) <= lenience 
-            || levenshtein(query, &res.slug) <= lenience 
-        })
+        matching::rank(query, &mut self.hits);
+    }
+
+    /// Returns true if modrinth has more results for this query 
+    /// than have been fetched so far
+    pub fn has_more(&self) -> bool {
+        self.offset + self.limit < self.total_hits
+    }
+
+    /// Function to add the next page of search results for the same query, 
+    /// results we already have are left out
+    pub fn extend(&mut self, mut next: SearchResponse) {
+        next.hits.retain(|r| !self.hits.iter().any(|h| h.slug == r.slug));
+        self.hits.append(&mut next.hits);
+        self.offset = next.offset;
+        self.limit = next.limit;
+        self.total_hits = next.total_hits;
     }
 
     /// Function that narrows down the search results of a response 
-    /// to a single one
-    pub fn pick_result(mut self) -> SearchResult {
+    /// to a single one, asking the user when there's more than one
+    pub fn pick_result(&mut self) -> Pick {
         let query = self.query.unwrap();
 
         if self.hits.is_empty() {
-            eprintln!("error: {} not found", query);
-            std::process::exit(1);
+            return Pick::NotFound;
         } else if self.hits.len() == 1 {
-            self.hits.swap_remove(0)
+            return Pick::Chosen(Box::new(self.hits.swap_remove(0)));
+        }
+
+        println!("\nResults for \"{}\":", query);
+        self.hits.iter().enumerate()
+            .for_each(|(i, r)| println!("{}", r.summary(i + 1)));
+
+        if self.has_more() {
+            print!("\nPick mod (default=1, s=skip, m=more): ");
         } else {
-            // Numbered list of results
-            self.hits.iter().enumerate()
-                .for_each(|(i, r)| println!("\t{}) {}", i+1, &r.title));
-
-            print!("\nPick mod (default=1): ");
-            std::io::stdout().flush().unwrap();
-
-            // string to hold user input
-            let mut input = String::with_capacity(2);
-
-            let choice = loop { // loop until they pick a mod
-                std::io::stdin().read_line(&mut input).unwrap();
-                if input.trim().is_empty() { break 1; }
-                match input.trim().parse::<usize>() {
-                    Ok(num) => { // parsing was good but is it a valid option?
-                        if num > self.hits.len() || num == 0 {
-                            eprint!("Invalid input, try again: ");
-                            continue;
-                        } else { break num }
-                    },
-                    Err(_)  => { // Parsing didn't work so restart loop
+            print!("\nPick mod (default=1, s=skip): ");
+        }
+        std::io::stdout().flush().unwrap();
+
+        // string to hold user input
+        let mut input = String::with_capacity(2);
+
+        let choice = loop { // loop until they pick a mod
+            input.clear();
+            std::io::stdin().read_line(&mut input).unwrap();
+            match input.trim() {
+                "" => break 1,
+                "s" | "S" => return Pick::Skip,
+                "m" | "M" if self.has_more() => return Pick::More,
+                "m" | "M" => {
+                    eprint!("No more results, try again: ");
+                    continue;
+                },
+                _ => {},
+            }
+
+            match input.trim().parse::<usize>() {
+                Ok(num) => { // parsing was good but is it a valid option?
+                    if num > self.hits.len() || num == 0 {
                         eprint!("Invalid input, try again: ");
                         continue;
-                    }
-                };
+                    } else { break num }
+                },
+                Err(_)  => { // Parsing didn't work so restart loop
+                    eprint!("Invalid input, try again: ");
+                    continue;
+                }
             };
+        };
 
-            self.hits.swap_remove(choice-1)
-        }
+        Pick::Chosen(Box::new(self.hits.swap_remove(choice-1)))
     }
 }
 
-fn levenshtein(s1: &str, s2:&str) -> usize {
-    let (short, long) = if s1.len() > s2.len() {
-        (s2, s1)
-    } else {
-        (s1, s2)
-    };
-
-    let n = long.len();
-
-    let mut costs: Vec<usize> = (0..n+1).collect();
-    for (i, c) in short.chars().enumerate() {
-        costs[0] = i + 1;
-        let mut corner = i;
-        for (j, c2) in long.chars().enumerate() {
-        let upper = costs[j + 1];
-            if c == c2 {
-                costs[j + 1] = corner;
-            } else {
-                costs[j + 1] = 1 + [costs[j], upper, corner].iter().min().unwrap();
-            }
-            corner = upper
-        }
+/// What the user decided to do with a list of search results
+pub enum Pick {
+    /// a result was chosen
+    Chosen(Box<SearchResult>),
+
+    /// the query should be skipped
+    Skip,
+
+    /// show the next page of results
+    More,
+
+    /// nothing matched the query
+    NotFound,
+}
+
+/// Loaders modrinth lists amongst a project's categories
+const LOADERS: [&str; 14] = [
+    "fabric", "forge", "quilt", "neoforge", "liteloader", "modloader", "rift",
+    "iris", "optifine", "canvas", "bukkit", "paper", "spigot", "datapack",
+];
+
+/// Formats large numbers the short way, 1234567 -> 1.2M
+fn short_number(n: usize) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_999 => format!("{:.1}K", n as f64 / 1_000.0),
+        _ => format!("{:.1}M", n as f64 / 1_000_000.0),
     }
-    costs[n]
 }
 
 //#[serde(skip)] tells the serde library to not 
@@ -161,7 +223,7 @@ fn levenshtein(s1: &str, s2:&str) -> usize {
 
 /// A Struct that represents the information given to the api caller contained 
 /// in the response of a search more info here: https://docs.modrinth.com/docs/tutorials/api_search/
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SearchResult {
     pub slug: String,
 
@@ -169,9 +231,8 @@ pub struct SearchResult {
 
     pub description: String,
 
-    #[serde(skip)]
-    #[serde(rename = "categories")]
-    _categories: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
 
     pub client_side: Support,
 
@@ -179,9 +240,7 @@ pub struct SearchResult {
 
     pub project_type: ProjectType,
 
-    #[serde(skip)]
-    #[serde(rename = "downloads")]
-    _downloads: usize,
+    pub downloads: usize,
 
     #[serde(skip)]
     #[serde(rename = "icon_url")]
@@ -191,13 +250,10 @@ pub struct SearchResult {
     #[serde(rename = "project_id")]
     _project_id: String,
 
-    #[serde(skip)]
-    #[serde(rename = "author")]
-    _author: String,
+    #[serde(default)]
+    pub author: String,
 
-    #[serde(skip)]
-    #[serde(rename = "follows")]
-    _follows: usize,
+    pub follows: usize,
 
     pub versions: Vec<String>,
 
@@ -205,9 +261,8 @@ pub struct SearchResult {
     #[serde(rename = "date_created")]
     _date_created: String,
 
-    #[serde(skip)]
-    #[serde(rename = "date_modified")]
-    _date_modified: String,
+    #[serde(default)]
+    pub date_modified: String,
 
     #[serde(skip)]
     #[serde(rename = "latest_version")]
@@ -222,6 +277,79 @@ pub struct SearchResult {
     _gallery: Option<Vec<String>>,
 }
 
+impl SearchResult {
+    /// The loaders this project supports, taken from its categories
+    pub fn loaders(&self) -> Vec<&str> {
+        self.categories.iter()
+            .map(String::as_str)
+            .filter(|c| LOADERS.contains(c))
+            .collect()
+    }
+
+    /// A few lines describing this result for picking it out of a 
+    /// numbered list of results
+    pub fn summary(&self, number: usize) -> String {
+        // keep the description to a single line
+        let mut description: String = self.description.chars().take(72).collect();
+        if description.len() < self.description.len() {
+            description.push_str("...");
+        }
+
+        // dates look like 2022-07-12T19:13:45.114325Z, the day is enough
+        let updated = self.date_modified.get(..10).unwrap_or(&self.date_modified);
+
+        format!(
+            "\t{}) {} ({}) by {}\n\t   {}\n\t   {} downloads | updated {} | {}",
+            number, self.title, self.slug, self.author,
+            description,
+            short_number(self.downloads), updated, self.loaders().join(", "),
+        )
+    }
+}
+
 // These are unit tests for this module
 #[cfg(test)]
-mod search_tests {}
+mod search_tests {
+    use super::*;
+    use crate::modrinth::MODRINTH_API;
+
+    #[test]
+    fn test_short_number() {
+        assert_eq!(short_number(0), "0");
+        assert_eq!(short_number(999), "999");
+        assert_eq!(short_number(1_250), "1.2K");
+        assert_eq!(short_number(12_345_678), "12.3M");
+    }
+
+    #[test]
+    fn test_url_facets() {
+        let versions = ["1.19.2".to_string()];
+        let search = Search::new(&[], &versions, ProjectType::Mod, vec!["fabric".to_string()]);
+        assert!(search.url(MODRINTH_API, "sodium", 0).ends_with(
+            "facets=[[\"versions:1.19.2\"],[\"project_type:mod\"],[\"categories:fabric\"]]"));
+
+        let loaders = ProjectType::Shader.loaders_for(Some("fabric"));
+        let search = Search::new(&[], &versions, ProjectType::Shader, loaders);
+        assert!(search.url(MODRINTH_API, "complementary", 5).contains("offset=5"));
+        assert!(search.url(MODRINTH_API, "complementary", 5).ends_with(
+            "[\"project_type:shader\"],[\"categories:iris\",\"categories:optifine\",\"categories:canvas\",\"categories:vanilla\"]]"));
+
+        // every version in a range is OR'd together
+        let versions = ["1.19.3".to_string(), "1.19.2".to_string()];
+        let search = Search::new(&[], &versions, ProjectType::Mod, Vec::new());
+        assert!(search.url(MODRINTH_API, "sodium", 0).ends_with(
+            "facets=[[\"versions:1.19.3\",\"versions:1.19.2\"],[\"project_type:mod\"]]"));
+    }
+
+    #[test]
+    fn test_has_more() {
+        let page = |offset, total_hits| serde_json::from_value::<SearchResponse>(serde_json::json!({
+            "hits": [], "offset": offset, "limit": 5, "total_hits": total_hits,
+        })).unwrap();
+
+        assert!(page(0, 12).has_more());
+        assert!(page(5, 12).has_more());
+        assert!(!page(10, 12).has_more());
+        assert!(!page(0, 5).has_more());
+    }
+}
@@ -1,19 +1,23 @@
-use serde::Deserialize;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
 
 // This module contains enums and structs used in multiple 
 // other structs that are constructed through HTTP GET requests
 
 /// Enum that represents a mod's dependency requirement
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     Required,
     Optional,
-    Incompatable,
+    Incompatible,
+    Embedded,
 }
 
 /// Enum that represents a mods client/server side support
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Support {
     Required,
@@ -21,57 +25,266 @@ pub enum Support {
     Unsupported
 }
 
-/// Enum that represents whether a project is a Mod or Modpack
-#[derive(Debug, Deserialize)]
+impl std::fmt::Display for Support {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Support::Required => "required",
+            Support::Optional => "optional",
+            Support::Unsupported => "unsupported",
+        })
+    }
+}
+
+/// Enum that represents which side of the game mods are being installed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Server,
+}
+
+impl Side {
+    /// The search facet that only matches projects that run on this side
+    pub fn facet(&self) -> &'static str {
+        match self {
+            Side::Client => "[\"client_side:required\",\"client_side:optional\"]",
+            Side::Server => "[\"server_side:required\",\"server_side:optional\"]",
+        }
+    }
+
+    /// Returns true if a project with the given support can run on this side
+    pub fn supported(&self, client_side: Support, server_side: Support) -> bool {
+        match self {
+            Side::Client => client_side != Support::Unsupported,
+            Side::Server => server_side != Support::Unsupported,
+        }
+    }
+}
+
+impl std::str::FromStr for Side {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "client" => Side::Client,
+            "server" => Side::Server,
+            _ => bail!("unknown side \"{s}\""),
+        })
+    }
+}
+
+/// Enum that represents what kind of project something is on modrinth
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ProjectType {
+    #[default]
     Mod,
-    Modpack
+    Modpack,
+    ResourcePack,
+    Shader,
+    Datapack,
+}
+
+impl ProjectType {
+    /// The name modrinth uses for this project type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectType::Mod => "mod",
+            ProjectType::Modpack => "modpack",
+            ProjectType::ResourcePack => "resourcepack",
+            ProjectType::Shader => "shader",
+            ProjectType::Datapack => "datapack",
+        }
+    }
+
+    /// The loaders projects of this type are published for, mods and 
+    /// modpacks return an empty slice because they use a mod loader
+    pub fn loaders(&self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Mod | ProjectType::Modpack => &[],
+            ProjectType::ResourcePack => &["minecraft"],
+            ProjectType::Shader => &["iris", "optifine", "canvas", "vanilla"],
+            ProjectType::Datapack => &["datapack"],
+        }
+    }
+
+    /// The loaders to filter by when looking for projects of this type, 
+    /// the user's `loader` is only used if it makes sense for the type
+    pub fn loaders_for(&self, loader: Option<&str>) -> Vec<String> {
+        match (self.loaders(), loader) {
+            ([], Some(loader)) => vec![loader.to_string()],
+            ([], None) => Vec::new(),
+            (loaders, Some(loader)) if loaders.contains(&loader) => vec![loader.to_string()],
+            (loaders, _) => loaders.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    /// The folder, relative to the minecraft directory, that projects 
+    /// of this type are installed to. Datapacks belong to a `world`
+    pub fn install_dir(&self, mc_dir: &Path, world: Option<&str>) -> Result<PathBuf> {
+        Ok(match self {
+            ProjectType::Mod => PathBuf::from("mods"),
+            ProjectType::ResourcePack => PathBuf::from("resourcepacks"),
+            ProjectType::Shader => PathBuf::from("shaderpacks"),
+            ProjectType::Datapack => match world {
+                Some(world) if mc_dir.join("saves").join(world).is_dir() => 
+                    ["saves", world, "datapacks"].iter().collect(),
+                Some(world) => bail!("there is no world named \"{world}\""),
+                None => bail!("datapacks are installed into a world, choose one with --world"),
+            },
+            ProjectType::Modpack => bail!("modpacks can't be installed yet"),
+        })
+    }
+}
+
+impl std::fmt::Display for ProjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for ProjectType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "mod" => ProjectType::Mod,
+            "modpack" => ProjectType::Modpack,
+            "resourcepack" => ProjectType::ResourcePack,
+            "shader" => ProjectType::Shader,
+            "datapack" => ProjectType::Datapack,
+            _ => bail!("unknown project type \"{s}\""),
+        })
+    }
 }
 
-// Enum that represents the project's status on modrinth
-#[derive(Deserialize)]
+/// Enum that represents the project's status on modrinth
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ProjectStatus {
     Approved,
+    Archived,
     Rejected,
     Draft,
     Unlisted,
-    Archived,
     Processing,
+    Withheld,
+    Scheduled,
+    Private,
+    #[default]
+    #[serde(other)]
+    Unknown
+}
+
+impl std::fmt::Display for ProjectStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            ProjectStatus::Approved => "approved",
+            ProjectStatus::Archived => "archived",
+            ProjectStatus::Rejected => "rejected",
+            ProjectStatus::Draft => "draft",
+            ProjectStatus::Unlisted => "unlisted",
+            ProjectStatus::Processing => "processing",
+            ProjectStatus::Withheld => "withheld",
+            ProjectStatus::Scheduled => "scheduled",
+            ProjectStatus::Private => "private",
+            ProjectStatus::Unknown => "unknown",
+        })
+    }
+}
+
+/// Enum that represents whether a version can be seen on modrinth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionStatus {
+    Listed,
+    Archived,
+    Draft,
+    Unlisted,
+    Scheduled,
+    #[serde(other)]
     Unknown
 }
 
+impl std::fmt::Display for VersionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            VersionStatus::Listed => "listed",
+            VersionStatus::Archived => "archived",
+            VersionStatus::Draft => "draft",
+            VersionStatus::Unlisted => "unlisted",
+            VersionStatus::Scheduled => "scheduled",
+            VersionStatus::Unknown => "unknown",
+        })
+    }
+}
+
 /// Struct containing an id, platform, and url for donations
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DonationInfo {
-    _id: String,
-    _platform: String,
-    _url: String,
+    /// ex: "patreon"
+    pub id: String,
+    pub platform: String,
+    pub url: String,
 }
 
 /// A struct containg a message from a moderator
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModeratorMessage {
-    _message: Option<String>,
-    _body: Option<String>,
+    pub message: String,
+    pub body: Option<String>,
 }
 
 /// Struct containing software License information
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct License {
-    _id: Option<String>,
-    _name: Option<String>,
-    _url: Option<String>,
+    /// SPDX identifier, ex: "LGPL-3.0-only"
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
 }
 
 /// struct containing the information about showcase pictures on the mod's 
 /// page on modrinth
-#[derive(Deserialize)] 
+#[derive(Debug, Clone, Deserialize, Serialize)] 
 pub struct Picture {
-    _url: String,
-    _featured: bool,
-    _title: Option<String>,
-    _description: Option<String>,
-    _creating: String
+    pub url: String,
+    pub featured: bool,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub created: String,
+    #[serde(default)]
+    pub ordering: i64,
+}
+
+/// The hash algorithms modrinth can look files up by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// The name modrinth uses for this algorithm
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+/// A minecraft version as listed by modrinth's `/tag/game_version`
+#[derive(Debug, Deserialize)]
+pub struct GameVersionTag {
+    pub version: String,
+
+    /// "release", "snapshot", "alpha" or "beta"
+    pub version_type: String,
+
+    pub date: String,
+
+    /// true for the first release of a major version, ex: 1.19
+    pub major: bool,
 }
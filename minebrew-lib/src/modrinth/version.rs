@@ -1,36 +1,65 @@
 use std::fmt::Display;
 
-use serde::Deserialize;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
 use super::shared::*;
+use super::project::parse_project_url;
+use crate::state::Pin;
 
-#[derive(Deserialize)]
-struct Dependency {
-    #[serde(skip)]
-    #[serde(rename = "version_id")]
-    _version_id: Option<String>,
+/// Another project or version a version depends on, or doesn't work with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Dependency {
+    #[serde(default)]
+    pub version_id: Option<String>,
+
+    #[serde(default)]
+    pub project_id: Option<String>,
 
-    #[serde(skip)]
-    #[serde(rename = "project_id")]
-    _project_id: Option<String>,
+    /// set for files bundled into the version that aren't on modrinth
+    #[serde(default)]
+    pub file_name: Option<String>,
 
-    #[serde(rename = "dependecy_type")]
-    _dependecy_type: DependencyType,
+    pub dependency_type: DependencyType,
 }
 
-#[derive(Deserialize)]
+/// How stable a version is, these are ordered from most to least stable
+/// so a version type also works as the least stable version to allow
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
-enum VersionType {
+pub enum VersionType {
+    #[default]
     Release,
     Beta,
     Alpha
 }
 
-#[derive(Deserialize)]
+impl std::fmt::Display for VersionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            VersionType::Release => "release",
+            VersionType::Beta => "beta",
+            VersionType::Alpha => "alpha",
+        })
+    }
+}
+
+impl std::str::FromStr for VersionType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "release" => VersionType::Release,
+            "beta" => VersionType::Beta,
+            "alpha" => VersionType::Alpha,
+            _ => bail!("unknown version type \"{s}\""),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModFile {
-    #[serde(skip)]
-    #[serde(rename = "hashes")]
-    _hashes: Hashes,
+    pub hashes: Hashes,
 
     pub url: String,
 
@@ -39,6 +68,10 @@ pub struct ModFile {
     pub primary: bool,
 
     pub size: usize,
+
+    /// what an extra file is for, ex: "required-resource-pack"
+    #[serde(default)]
+    pub file_type: Option<String>,
 }
 
 impl Display for ModFile {
@@ -47,64 +80,75 @@ impl Display for ModFile {
     }
 }
 
-#[derive(Deserialize, Default)]
+/// A version of a mod that has been chosen for installation
+pub struct Candidate {
+    pub slug: String,
+
+    pub version: Version,
+
+    /// the file that will be downloaded
+    pub file: ModFile,
+
+    /// the newest version number, if a pin is keeping us on an older one
+    pub held_back: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct Hashes {
-    _sha512: String,
-    _sha1: String,
+    #[serde(default)]
+    pub sha512: String,
+
+    #[serde(default)]
+    pub sha1: String,
 }
 
 #[derive(Deserialize)]
-pub struct Versions (Vec<Version>);
+pub struct Versions (pub Vec<Version>);
 
 /// struct that represents a particular downloadable version of a mod
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Version {
+    pub id: String,
+
+    pub project_id: String,
+
+    #[serde(default)]
+    pub author_id: String,
+
     pub name: String,
 
-    #[serde(skip)]
-    #[serde(rename = "version_number")]
-    _version_number: String,
+    pub version_number: String,
 
-    #[serde(skip)]
-    #[serde(rename = "changelog")]
-    _changelog: Option<String>,
+    /// in markdown
+    #[serde(default)]
+    pub changelog: Option<String>,
 
-    #[serde(skip)]
-    #[serde(rename = "dependencies")]
-    _dependencies: Option<Vec<Dependency>>,
+    #[serde(default)]
+    pub changelog_url: Option<String>,
 
-    pub game_versions: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
 
-    #[serde(rename = "version_type")]
-    _version_type: VersionType,
+    pub game_versions: Vec<String>,
 
-    #[serde(skip)]
-    #[serde(rename = "loaders")]
-    _loaders: Vec<String>,
+    pub version_type: VersionType,
 
-    #[serde(skip)]
-    #[serde(rename = "featured")]
-    _featured: bool,
+    pub loaders: Vec<String>,
 
-    #[serde(skip)]
-    #[serde(rename = "id")]
-    _id: String,
+    #[serde(default)]
+    pub featured: bool,
 
-    #[serde(skip)]
-    #[serde(rename = "project_id")]
-    _project_id: String,
+    #[serde(default)]
+    pub status: Option<VersionStatus>,
 
-    #[serde(skip)]
-    #[serde(rename = "author_id")]
-    _author_id: String,
+    #[serde(default)]
+    pub requested_status: Option<VersionStatus>,
 
-    #[serde(skip)]
-    #[serde(rename = "date_published")]
-    _date_published: String,
+    #[serde(default)]
+    pub date_published: String,
 
-    #[serde(skip)]
-    #[serde(rename = "changelog_url")]
-    _changelog_url: Option<String>,
+    #[serde(default)]
+    pub downloads: usize,
 
     pub files: Vec<ModFile>,
 }
@@ -116,28 +160,181 @@ impl std::fmt::Display for Version {
     }
 }
 
+/// Splits a `slug@version` query into the slug and the requested 
+/// version, queries without a version are returned untouched. 
+/// Pasted modrinth.com project urls are reduced to their slug
+pub fn parse_query(query: &str) -> (&str, Option<&str>) {
+    if let Some(parsed) = parse_project_url(query) {
+        return parsed;
+    }
+
+    match query.split_once('@') {
+        Some((slug, version)) if !slug.is_empty() && !version.is_empty() => (slug, Some(version)),
+        _ => (query, None),
+    }
+}
+
 impl Version {
-    pub async fn search(slug: &str, version: &str) -> Result<Vec<Version>, reqwest::Error> { 
-        let json_str = match reqwest::get(format!("https://api.modrinth.com/v2/project/{}/version?game_versions=[\"{}\"]", slug, version)).await {
-            Err(e) => { // Handle ERROR case for GET request
-                // if it is a connection error then let the user know
-                if e.is_connect() {
-                    eprintln!("Error connecting to host...");
-                    std::process::exit(1)
-                // If it isnt then something else happened that shouldnt have
-                } else {
-                    panic!("Unexpected reqwest error...")
-                }
+    /// Returns true if `query` is this version's id or version number
+    pub fn matches(&self, query: &str) -> bool {
+        self.id == query || self.version_number == query
+    }
+
+    /// Make sure this version can be installed for one of the target 
+    /// minecraft versions and, if any were chosen, one of the loaders
+    pub fn check_compatible(&self, slug: &str, targets: &[String], loaders: &[String]) -> Result<()> {
+        if !self.game_versions.iter().any(|v| targets.contains(v)) {
+            bail!("{slug} {} doesn't support minecraft {} (supports {})",
+                self.version_number, targets.join(" or "), self.game_versions.join(", "));
+        }
+
+        if !loaders.is_empty() && !self.loaders.iter().any(|l| loaders.contains(l)) {
+            bail!("{slug} {} doesn't support {} (supports {})",
+                self.version_number, loaders.join(" or "), self.loaders.join(", "));
+        }
+
+        Ok(())
+    }
+
+    /// Consume the version and return its primary file, or the
+    /// first file if none of them are marked as primary
+    pub fn primary_file(&mut self) -> ModFile {
+        match self.files.iter().position(|f| f.primary) {
+            Some(i) => self.files.swap_remove(i),
+            None => self.files.swap_remove(0),
+        }
+    }
+
+    /// Choose which version of a mod to install from a list of versions 
+    /// sorted newest first, respecting the version it may be pinned to. 
+    /// Versions less stable than `channel` are ignored unless pinned
+    pub fn select(slug: &str, mut versions: Vec<Version>, pin: Option<&Pin>, channel: VersionType) -> Result<Candidate> {
+        versions.retain(|v| v.version_type <= channel || pin.is_some_and(|p| v.matches(&p.version_id)));
+
+        if versions.is_empty() {
+            bail!("no versions of {slug} at or above {channel} stability are available for the target");
+        }
+
+        let (index, held_back) = match pin {
+            None => (0, None),
+            Some(pin) => match versions.iter().position(|v| v.matches(&pin.version_id)) {
+                Some(0) => (0, None),
+                Some(i) => (i, Some(versions[0].version_number.clone())),
+                None => bail!("{slug} is pinned to {} which isn't available for the target, \
+                    run `mbrew unpin {slug}` to allow other versions", pin.version_number),
             },
-            Ok(response) => response.text().await.unwrap() // Parse json on success
         };
- 
-        let versions: Vec<Version> = serde_json::from_str(&json_str).unwrap();
-        Ok(versions)
+
+        let mut version = versions.swap_remove(index);
+        let file = version.primary_file();
+        Ok(Candidate { slug: slug.to_string(), version, file, held_back })
+    }
+
+    /// The versions from a list sorted newest first that came out after 
+    /// `installed`, up to and including the one with the id `candidate`. 
+    /// These are the changes upgrading from `installed` brings, empty 
+    /// when `candidate` isn't in the list
+    pub fn changes_between<'a>(versions: &'a [Version], installed: &Version, candidate: &str) -> &'a [Version] {
+        let start = match versions.iter().position(|v| v.id == candidate) {
+            Some(start) => start,
+            None => return &[],
+        };
+
+        // the installed version might not be listed if the target changed 
+        // since, then there's no telling what's new so only the candidate is
+        let end = versions.iter()
+            .position(|v| v.id == installed.id)
+            .unwrap_or(start + 1);
+
+        versions.get(start..end).unwrap_or_default()
     }
 
-    pub async fn download_file(url: &str) -> Vec<u8> {
-        let res = reqwest::get(url).await.unwrap();
-        res.bytes().await.unwrap().to_vec()
+    /// The newest version for every loader and minecraft version from a 
+    /// list of versions sorted newest first, grouped by loader. Minecraft 
+    /// versions are listed in the order they first show up, so newest first
+    pub fn latest_per_target(versions: &[Version]) -> Vec<(&str, Vec<(&str, &Version)>)> {
+        let mut latest: Vec<(&str, Vec<(&str, &Version)>)> = Vec::new();
+        for version in versions {
+            for loader in &version.loaders {
+                let i = match latest.iter().position(|(l, _)| l == loader) {
+                    Some(i) => i,
+                    None => {
+                        latest.push((loader, Vec::new()));
+                        latest.len() - 1
+                    },
+                };
+
+                // a version lists the minecraft versions it supports oldest first
+                let targets = &mut latest[i].1;
+                for game_version in version.game_versions.iter().rev() {
+                    if !targets.iter().any(|(g, _)| g == game_version) {
+                        targets.push((game_version, version));
+                    }
+                }
+            }
+        }
+        latest
+    }
+}
+
+#[cfg(test)]
+mod version_tests {
+    use super::{parse_query, Version};
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(parse_query("sodium"), ("sodium", None));
+        assert_eq!(parse_query("sodium@0.4.10"), ("sodium", Some("0.4.10")));
+        assert_eq!(parse_query("sodium@mc1.19.2-0.4.4"), ("sodium", Some("mc1.19.2-0.4.4")));
+        assert_eq!(parse_query("sodium@"), ("sodium@", None));
+        assert_eq!(parse_query("@0.4.10"), ("@0.4.10", None));
+        assert_eq!(parse_query("https://modrinth.com/mod/sodium"), ("sodium", None));
+    }
+
+    fn version(number: &str, loaders: &[&str], game_versions: &[&str]) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "id": number, "project_id": "AABBCCDD", "name": number, "version_number": number,
+            "game_versions": game_versions, "version_type": "release", "loaders": loaders, "files": [],
+            "date_published": "2023-01-01T00:00:00Z",
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_changes_between() {
+        let versions = [
+            version("0.5.4", &["fabric"], &["1.20.1"]),
+            version("0.5.3", &["fabric"], &["1.20.1"]),
+            version("0.5.2", &["fabric"], &["1.20.1"]),
+            version("0.5.1", &["fabric"], &["1.20.1"]),
+        ];
+        let numbers = |changes: &[Version]| changes.iter().map(|v| v.version_number.clone()).collect::<Vec<_>>();
+
+        assert_eq!(numbers(Version::changes_between(&versions, &versions[3], "0.5.3")), ["0.5.3", "0.5.2"]);
+        assert!(Version::changes_between(&versions, &versions[0], "0.5.4").is_empty());
+
+        // installed for a different target, only the candidate's changes are known
+        let other = version("0.5.2", &["fabric"], &["1.19.4"]);
+        let other = Version { id: "other".to_string(), ..other };
+        assert_eq!(numbers(Version::changes_between(&versions, &other, "0.5.4")), ["0.5.4"]);
+
+        // the candidate isn't listed
+        assert!(Version::changes_between(&versions, &versions[3], "missing").is_empty());
+    }
+
+    #[test]
+    fn test_latest_per_target() {
+        let versions = [
+            version("0.5.3", &["fabric", "quilt"], &["1.20", "1.20.1"]),
+            version("0.5.2", &["fabric"], &["1.20.1"]),
+            version("0.4.10", &["fabric"], &["1.19.4"]),
+        ];
+
+        let latest: Vec<(&str, Vec<(&str, &str)>)> = Version::latest_per_target(&versions).into_iter()
+            .map(|(l, targets)| (l, targets.into_iter().map(|(g, v)| (g, v.version_number.as_str())).collect()))
+            .collect();
+        assert_eq!(latest, vec![
+            ("fabric", vec![("1.20.1", "0.5.3"), ("1.20", "0.5.3"), ("1.19.4", "0.4.10")]),
+            ("quilt", vec![("1.20.1", "0.5.3"), ("1.20", "0.5.3")]),
+        ]);
     }
 }
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+// Minebrew never prints anything itself while it works, it tells a `Progress`
+// what it's doing instead. The command line draws a bar for every file from
+// these, anything else using the library can show them however it likes or
+// log them. Every method does nothing unless it's overridden
+
+/// Hooks called as mods are resolved, downloaded and installed
+pub trait Progress: Send + Sync {
+    /// Started looking for the version of `slug` to install
+    fn resolve_started(&self, _slug: &str) {}
+
+    /// Started downloading a file that's `size` bytes, `resumed_from`
+    /// of them were already downloaded by an earlier try
+    fn file_started(&self, _filename: &str, _size: u64, _resumed_from: u64) {}
+
+    /// `downloaded` bytes of a file have arrived so far
+    fn file_progress(&self, _filename: &str, _downloaded: u64) {}
+
+    /// Every byte of a file has arrived
+    fn file_finished(&self, _filename: &str) {}
+
+    /// Checking a downloaded file against the hash modrinth has for it
+    fn verify(&self, _filename: &str) {}
+
+    /// A file was put in place at `dest`, `cached` when
+    /// it came from the cache instead of being downloaded
+    fn install(&self, _filename: &str, _dest: &Path, _cached: bool) {}
+}
+
+/// Ignores every event
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
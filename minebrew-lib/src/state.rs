@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::modrinth::{ModFile, ProjectType, Version, normalize};
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the file inside the minecraft directory that holds Minebrew's state
+const STATE_FILE: &str = "minebrew.json";
+
+/// Record of a mod that Minebrew has installed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledMod {
+    pub project_id: String,
+    pub version_id: String,
+    pub version_number: String,
+    pub filename: String,
+
+    /// folder the file was installed to, relative to the minecraft directory
+    #[serde(default = "default_folder")]
+    pub folder: PathBuf,
+}
+
+fn default_folder() -> PathBuf { PathBuf::from("mods") }
+
+/// The version a mod has been pinned to. Versions are matched against
+/// the id since version numbers aren't unique, the number is only
+/// kept to show to the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "PinEntry")]
+pub struct Pin {
+    pub version_id: String,
+    pub version_number: String,
+}
+
+impl Pin {
+    pub fn new(version: &Version) -> Self {
+        Self { version_id: version.id.clone(), version_number: version.version_number.clone() }
+    }
+}
+
+/// Older state files stored pins as a bare version id or number
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PinEntry {
+    Pin { version_id: String, version_number: String },
+    Legacy(String),
+}
+
+impl From<PinEntry> for Pin {
+    fn from(entry: PinEntry) -> Self {
+        match entry {
+            PinEntry::Pin { version_id, version_number } => Self { version_id, version_number },
+            PinEntry::Legacy(version) => Self { version_id: version.clone(), version_number: version },
+        }
+    }
+}
+
+/// Minebrew's state for a single minecraft directory, this is
+/// what we know about the mods we've installed and the versions
+/// the user has pinned them to
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// installed mods keyed by their slug
+    #[serde(default)]
+    pub installed: BTreeMap<String, InstalledMod>,
+
+    /// pinned mods keyed by their slug
+    #[serde(default)]
+    pub pins: BTreeMap<String, Pin>,
+
+    /// slugs the user picked out of several search results, keyed by 
+    /// the project type and normalized query that was searched for
+    #[serde(default)]
+    pub choices: BTreeMap<String, String>,
+
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl State {
+    /// Load the state file from a minecraft directory, if there isn't
+    /// one yet an empty state is returned
+    pub fn load(mc_dir: &Path) -> Result<Self> {
+        let path = mc_dir.join(STATE_FILE);
+
+        let mut state: State = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Error parsing \"{}\"", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(e).with_context(|| format!("Error reading \"{}\"", path.display())),
+        };
+
+        state.path = path;
+        Ok(state)
+    }
+
+    /// Write the state back to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("Error writing \"{}\"", self.path.display()))
+    }
+
+    /// Find the slug of an installed mod from either its slug or project id
+    pub fn find_slug(&self, name: &str) -> Option<&str> {
+        self.installed.iter()
+            .find(|(slug, m)| slug.as_str() == name || m.project_id == name)
+            .map(|(slug, _)| slug.as_str())
+    }
+
+    /// Returns the version a mod is pinned to, if it has been pinned
+    pub fn pin(&self, slug: &str) -> Option<&Pin> {
+        self.pins.get(slug)
+    }
+
+    /// Returns the slug the user picked the last time they searched
+    /// for `query` amongst projects of `project_type`
+    pub fn choice(&self, project_type: ProjectType, query: &str) -> Option<&str> {
+        self.choices.get(&choice_key(project_type, query)).map(String::as_str)
+    }
+
+    /// Remember which search result the user picked for `query`
+    pub fn remember_choice(&mut self, project_type: ProjectType, query: &str, slug: &str) {
+        self.choices.insert(choice_key(project_type, query), slug.to_string());
+    }
+
+    /// Forget the search result picked for `query`, returns the slug that 
+    /// had been picked if there was one
+    pub fn forget_choice(&mut self, project_type: ProjectType, query: &str) -> Option<String> {
+        self.choices.remove(&choice_key(project_type, query))
+    }
+
+    /// Record a newly installed version of a mod and the 
+    /// folder, relative to the minecraft directory, it went in
+    pub fn record(&mut self, slug: &str, version: &Version, file: &ModFile, folder: &Path) {
+        self.installed.insert(slug.to_string(), InstalledMod {
+            project_id: version.project_id.clone(),
+            version_id: version.id.clone(),
+            version_number: version.version_number.clone(),
+            filename: file.filename.clone(),
+            folder: folder.to_path_buf(),
+        });
+    }
+}
+
+/// "shader:complementary", a query means something different for every type
+fn choice_key(project_type: ProjectType, query: &str) -> String {
+    format!("{project_type}:{}", normalize(query))
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+
+    #[test]
+    fn test_choices() {
+        let mut state = State::default();
+        state.remember_choice(ProjectType::Mod, "Mini Map", "xaeros-minimap");
+
+        assert_eq!(state.choice(ProjectType::Mod, "mini  map"), Some("xaeros-minimap"));
+        assert_eq!(state.choice(ProjectType::ResourcePack, "mini map"), None);
+
+        assert_eq!(state.forget_choice(ProjectType::Mod, "mini map").as_deref(), Some("xaeros-minimap"));
+        assert_eq!(state.choice(ProjectType::Mod, "mini map"), None);
+    }
+
+    #[test]
+    fn test_legacy_pins() {
+        let json = r#"{ "pins": {
+            "sodium": "mc1.20.1-0.5.3",
+            "iris": { "version_id": "s5eFLITc", "version_number": "1.6.4+1.20.1" }
+        } }"#;
+        let state: State = serde_json::from_str(json).unwrap();
+
+        assert_eq!(state.pin("sodium").unwrap().version_id, "mc1.20.1-0.5.3");
+        assert_eq!(state.pin("iris").unwrap().version_number, "1.6.4+1.20.1");
+    }
+}